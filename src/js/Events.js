@@ -0,0 +1,50 @@
+/**
+ * Event types, as written by the engine. See `event.rs` for the layout of each event.
+ */
+export const EventType = {
+	jointBreak: 0,
+	collisionBegin: 1,
+	collisionPersist: 2,
+	collisionEnd: 3,
+};
+const collisionTypes = ["collisionBegin", "collisionPersist", "collisionEnd"];
+
+/**
+ * Decodes the flat array returned by `engine.drain_events()` into event objects.
+ * Joint breaks are always recorded. Collision events (begin / persist / end) are off by default, so without
+ * `engine.world_set_record_events(true)` they never show up here, silently. Once that's on, drain every step or the queue keeps growing.
+ * @param {Float32Array} data - Array from `engine.drain_events()`
+ * @return {Array<Object>} Events, each with a `type` field matching a key of `EventType`
+ */
+export function decodeEvents(data) {
+	let events = [];
+	let i = 0;
+	while (i < data.length) {
+		let type = data[i++];
+		if (type === EventType.jointBreak) {
+			events.push({
+				type: "jointBreak",
+				joint: data[i], bodyA: data[i + 1], bodyB: data[i + 2],
+				force: data[i + 3], torque: data[i + 4],
+			});
+			i += 5;
+		}
+		else {
+			let contactCount = data[i + 6];
+			let contacts = [];
+			for (let c = 0; c < contactCount; c++) {
+				contacts.push({ x: data[i + 7 + c * 2], y: data[i + 8 + c * 2] });
+			}
+			events.push({
+				type: collisionTypes[type - 1],
+				bodyA: data[i], bodyB: data[i + 1],
+				normal: { x: data[i + 2], y: data[i + 3] },
+				approachSpeed: data[i + 4],
+				impulse: data[i + 5],
+				contacts,
+			});
+			i += 7 + contactCount * 2;
+		}
+	}
+	return events;
+}
@@ -62,8 +62,8 @@ export class Render {
 		let grid = renderGrid ? engine.world_get_grid() : {};
 		
 		// Render broadphase grid
-		if (renderGrid) {
-			const gridSize = 100; // from engine code; TODO: get this value from engine
+		const gridSize = renderGrid ? engine.world_get_bucket_size() : undefined; // undefined if the broadphase isn't a grid
+		if (renderGrid && gridSize !== undefined) {
 			const margin = 2;
 
 			// console.log(grid);
@@ -1,5 +1,6 @@
 export * from "wasm";
 export * from "./Ticker";
 export * from "./Render";
+export * from "./Events";
 
 // raw memory found under wasm.__wbindgen_memory()
\ No newline at end of file
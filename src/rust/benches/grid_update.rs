@@ -0,0 +1,52 @@
+// Compares Grid::update_body against removing and re-inserting every body, which is what it used to do
+// Run with `cargo bench --bench grid_update`
+use std::time::{Duration, Instant};
+
+use wasm::{Body, BodyOptions, Grid, Vec2};
+
+const BODY_COUNT: usize = 5000;
+const FRAMES: usize = 120;
+const BUCKET_SIZE: f32 = 100.0;
+
+fn create_bodies() -> Vec<Body> {
+	// Spread bodies over a square, about 2 per bucket
+	let side = (BODY_COUNT as f32).sqrt().ceil() as usize;
+	(0..BODY_COUNT)
+		.map(|i| {
+			let position = Vec2::new((i % side) as f32 * 70.0, (i / side) as f32 * 70.0);
+			let mut body = Body::rectangle(30.0, 30.0, position, BodyOptions::default());
+			// Mix of speeds, so some bodies stay in the same buckets for a while and others change every frame
+			body.velocity = Vec2::new(((i % 7) as f32 - 3.0) * 2.0, ((i % 5) as f32 - 2.0) * 3.0);
+			body
+		})
+		.collect()
+}
+
+fn run<F>(mut update: F) -> Duration where F: FnMut(&mut Grid, &mut Body) {
+	let mut grid = Grid::new(BUCKET_SIZE);
+	let mut bodies = create_bodies();
+	for body in bodies.iter_mut() {
+		grid.insert_body(body);
+	}
+
+	let start = Instant::now();
+	for _ in 0..FRAMES {
+		for body in bodies.iter_mut() {
+			let velocity = *body.get_velocity();
+			body.translate_position(velocity);
+			update(&mut grid, body);
+		}
+	}
+	start.elapsed()
+}
+
+fn main() {
+	let full = run(|grid, body| grid.insert_body(body));
+	let incremental = run(|grid, body| grid.update_body(body));
+
+	let per_frame = |duration: Duration| duration.as_secs_f64() * 1000.0 / FRAMES as f64;
+	println!("{} bodies, {} frames", BODY_COUNT, FRAMES);
+	println!("re-insert:   {:.3} ms / frame", per_frame(full));
+	println!("incremental: {:.3} ms / frame", per_frame(incremental));
+	println!("speedup:     {:.2}x", full.as_secs_f64() / incremental.as_secs_f64());
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use nohash_hasher::BuildNoHashHasher;
+
+use crate::{Body, BodyMap, Bounds, Broadphase, Geo, Id, Vec2};
+
+const NULL: usize = usize::MAX; // no node
+
+// Node of the tree. Leaves hold one body each, branches always have two children
+struct Node {
+	bounds: Bounds, // fattened bounds for leaves, union of children for branches
+	parent: usize,
+	child_a: usize,
+	child_b: usize,
+	height: i32, // 0 for leaves
+	body: Id, // only meaningful for leaves
+}
+
+impl Node {
+	fn is_leaf(&self) -> bool {
+		self.child_a == NULL
+	}
+}
+
+// Dynamic AABB tree broadphase. Handles bodies of very different sizes better than the grid, since a body is always a single leaf
+// Leaves store bounds fattened by margin, so bodies that move a little don't have to be moved in the tree
+pub struct AabbTree {
+	pub margin: Geo,
+	nodes: Vec<Node>,
+	free: Vec<usize>, // indices of unused nodes, reused before growing nodes
+	root: usize,
+	leaves: HashMap<Id, usize, BuildNoHashHasher<Id>>,
+}
+
+impl AabbTree {
+	pub fn new(margin: Geo) -> Self {
+		Self {
+			margin,
+			nodes: Vec::new(),
+			free: Vec::new(),
+			root: NULL,
+			leaves: HashMap::with_hasher(BuildNoHashHasher::default()),
+		}
+	}
+
+	//
+	// bodies
+	//
+
+	pub fn insert_body(&mut self, body: &Body) {
+		if self.leaves.contains_key(&body.id) {
+			self.remove_body(body);
+		}
+		let leaf = self.allocate(body.bounds.fattened(self.margin), body.id);
+		self.insert_leaf(leaf);
+		self.leaves.insert(body.id, leaf);
+	}
+	pub fn remove_body(&mut self, body: &Body) {
+		let Some(leaf) = self.leaves.remove(&body.id) else { return };
+		self.remove_leaf(leaf);
+		self.free.push(leaf);
+	}
+	// Only moves the body in the tree once it leaves its fattened bounds
+	pub fn update_body(&mut self, body: &Body) {
+		let Some(&leaf) = self.leaves.get(&body.id) else {
+			self.insert_body(body);
+			return;
+		};
+		if self.nodes[leaf].bounds.contains(&body.bounds) { return; }
+
+		self.remove_leaf(leaf);
+		self.nodes[leaf].bounds = body.bounds.fattened(self.margin);
+		self.insert_leaf(leaf);
+	}
+
+	//
+	// queries
+	//
+
+	// Ids of every body whose fattened bounds overlap bounds
+	pub fn query_aabb(&self, bounds: &Bounds) -> Vec<Id> {
+		let mut ids = Vec::new();
+		self.visit(|node_bounds| node_bounds.overlaps_with(bounds), |body_id| ids.push(body_id));
+		ids
+	}
+	// Adds pairs of bodies whose bounds overlap to pairs, each pair once
+	pub fn find_pairs(&self, bodies: &BodyMap, pairs: &mut Vec<(Id, Id)>) {
+		for body_id in self.leaves.keys() {
+			let body = bodies.get(body_id).expect("failed to get body in AabbTree::find_pairs");
+			if body.is_static { continue; } // pairs with dynamic bodies are found from the dynamic side
+			self.visit(|node_bounds| node_bounds.overlaps_with(&body.bounds), |other_id| {
+				if other_id == *body_id { return; }
+				let other = bodies.get(&other_id).expect("failed to get other body in AabbTree::find_pairs");
+				if !other.is_static && other_id < *body_id { return; } // other half of the pair
+				if body.bounds.overlaps_with(&other.bounds) {
+					pairs.push((*body_id, other_id));
+				}
+			});
+		}
+	}
+	// Calls visit once with every body whose fattened bounds the ray hits within max_distance. direction must be normalized
+	// Same signature as Grid::traverse_ray; there's no order to the bodies, so the exit distance is always max_distance
+	pub fn traverse_ray<F>(&self, origin: &Vec2, direction: &Vec2, max_distance: Geo, mut visit: F) where F: FnMut(&Vec<Id>, Geo) -> bool {
+		let mut ids = Vec::new();
+		self.visit(|node_bounds| node_bounds.raycast(origin, direction, max_distance).is_some(), |body_id| ids.push(body_id));
+		visit(&ids, max_distance);
+	}
+	// Walks down every branch enter_node accepts, calling visit_leaf on the accepted leaves
+	fn visit<E, V>(&self, enter_node: E, mut visit_leaf: V) where E: Fn(&Bounds) -> bool, V: FnMut(Id) {
+		if self.root == NULL { return; }
+		let mut stack = vec![self.root];
+		while let Some(index) = stack.pop() {
+			let node = &self.nodes[index];
+			if !enter_node(&node.bounds) { continue; }
+			if node.is_leaf() {
+				visit_leaf(node.body);
+			}
+			else {
+				stack.push(node.child_a);
+				stack.push(node.child_b);
+			}
+		}
+	}
+
+	//
+	// tree structure, based on Box2D's b2DynamicTree
+	//
+
+	fn allocate(&mut self, bounds: Bounds, body: Id) -> usize {
+		let node = Node {
+			bounds,
+			parent: NULL,
+			child_a: NULL,
+			child_b: NULL,
+			height: 0,
+			body,
+		};
+		if let Some(index) = self.free.pop() {
+			self.nodes[index] = node;
+			index
+		}
+		else {
+			self.nodes.push(node);
+			self.nodes.len() - 1
+		}
+	}
+	fn insert_leaf(&mut self, leaf: usize) {
+		if self.root == NULL {
+			self.root = leaf;
+			self.nodes[leaf].parent = NULL;
+			return;
+		}
+
+		// Find the best sibling, going down whichever side makes the tree's total perimeter grow the least
+		let leaf_bounds = self.nodes[leaf].bounds.clone();
+		let mut index = self.root;
+		while !self.nodes[index].is_leaf() {
+			let node = &self.nodes[index];
+			let perimeter = node.bounds.perimeter();
+			let combined_perimeter = node.bounds.union(&leaf_bounds).perimeter();
+
+			let cost = 2.0 * combined_perimeter; // cost of making a new parent for this node and the leaf
+			let inheritance_cost = 2.0 * (combined_perimeter - perimeter); // minimum cost of pushing the leaf further down
+			let child_cost = |child: usize| {
+				let child = &self.nodes[child];
+				let combined = child.bounds.union(&leaf_bounds).perimeter();
+				if child.is_leaf() { combined + inheritance_cost } else { combined - child.bounds.perimeter() + inheritance_cost }
+			};
+			let cost_a = child_cost(node.child_a);
+			let cost_b = child_cost(node.child_b);
+
+			if cost < cost_a && cost < cost_b { break; }
+			index = if cost_a < cost_b { node.child_a } else { node.child_b };
+		}
+		let sibling = index;
+
+		// New parent for the sibling and the leaf
+		let old_parent = self.nodes[sibling].parent;
+		let new_parent = self.allocate(leaf_bounds.union(&self.nodes[sibling].bounds), 0);
+		self.nodes[new_parent].parent = old_parent;
+		self.nodes[new_parent].height = self.nodes[sibling].height + 1;
+		self.nodes[new_parent].child_a = sibling;
+		self.nodes[new_parent].child_b = leaf;
+		self.nodes[sibling].parent = new_parent;
+		self.nodes[leaf].parent = new_parent;
+		if old_parent == NULL {
+			self.root = new_parent;
+		}
+		else {
+			self.replace_child(old_parent, sibling, new_parent);
+		}
+
+		self.refit(new_parent);
+	}
+	fn remove_leaf(&mut self, leaf: usize) {
+		if leaf == self.root {
+			self.root = NULL;
+			return;
+		}
+
+		// Replace the leaf's parent with its sibling
+		let parent = self.nodes[leaf].parent;
+		let grandparent = self.nodes[parent].parent;
+		let sibling = if self.nodes[parent].child_a == leaf { self.nodes[parent].child_b } else { self.nodes[parent].child_a };
+		self.nodes[sibling].parent = grandparent;
+		self.free.push(parent);
+		if grandparent == NULL {
+			self.root = sibling;
+		}
+		else {
+			self.replace_child(grandparent, parent, sibling);
+			self.refit(grandparent);
+		}
+	}
+	fn replace_child(&mut self, parent: usize, old_child: usize, new_child: usize) {
+		if self.nodes[parent].child_a == old_child {
+			self.nodes[parent].child_a = new_child;
+		}
+		else {
+			self.nodes[parent].child_b = new_child;
+		}
+	}
+	// Rebalances and recalculates the bounds / height of index and everything above it
+	fn refit(&mut self, mut index: usize) {
+		while index != NULL {
+			index = self.balance(index);
+			let (child_a, child_b) = (self.nodes[index].child_a, self.nodes[index].child_b);
+			self.nodes[index].height = 1 + self.nodes[child_a].height.max(self.nodes[child_b].height);
+			self.nodes[index].bounds = self.nodes[child_a].bounds.union(&self.nodes[child_b].bounds);
+			index = self.nodes[index].parent;
+		}
+	}
+	// If one side of a is more than 1 taller than the other, rotates the taller child up to take a's place
+	// Returns the index of the node now in a's place
+	fn balance(&mut self, a: usize) -> usize {
+		if self.nodes[a].is_leaf() || self.nodes[a].height < 2 { return a; }
+
+		let b = self.nodes[a].child_a;
+		let c = self.nodes[a].child_b;
+		let balance = self.nodes[c].height - self.nodes[b].height;
+		if balance > 1 {
+			self.rotate_up(a, c, b, false);
+			c
+		}
+		else if balance < -1 {
+			self.rotate_up(a, b, c, true);
+			b
+		}
+		else {
+			a
+		}
+	}
+	// Moves child (a child of a) up into a's place. a keeps other_child, and takes the shorter of child's children
+	// child_was_a is whether child was a's child_a
+	fn rotate_up(&mut self, a: usize, child: usize, other_child: usize, child_was_a: bool) {
+		let f = self.nodes[child].child_a;
+		let g = self.nodes[child].child_b;
+
+		// child takes a's place
+		let parent = self.nodes[a].parent;
+		self.nodes[child].child_a = a;
+		self.nodes[child].parent = parent;
+		self.nodes[a].parent = child;
+		if parent == NULL {
+			self.root = child;
+		}
+		else {
+			self.replace_child(parent, a, child);
+		}
+
+		// child keeps its taller child, a gets the shorter one in child's old spot
+		let (taller, shorter) = if self.nodes[f].height > self.nodes[g].height { (f, g) } else { (g, f) };
+		self.nodes[child].child_b = taller;
+		if child_was_a {
+			self.nodes[a].child_a = shorter;
+		}
+		else {
+			self.nodes[a].child_b = shorter;
+		}
+		self.nodes[shorter].parent = a;
+
+		self.nodes[a].bounds = self.nodes[other_child].bounds.union(&self.nodes[shorter].bounds);
+		self.nodes[a].height = 1 + self.nodes[other_child].height.max(self.nodes[shorter].height);
+		self.nodes[child].bounds = self.nodes[a].bounds.union(&self.nodes[taller].bounds);
+		self.nodes[child].height = 1 + self.nodes[a].height.max(self.nodes[taller].height);
+	}
+}
+
+impl Broadphase for AabbTree {
+	fn insert(&mut self, body: &mut Body) {
+		self.insert_body(body);
+	}
+	fn remove(&mut self, body: &mut Body) {
+		self.remove_body(body);
+	}
+	fn update(&mut self, body: &mut Body) {
+		self.update_body(body);
+	}
+	fn pairs(&self, bodies: &BodyMap, pairs: &mut Vec<(Id, Id)>) {
+		self.find_pairs(bodies, pairs)
+	}
+	fn query_aabb(&self, bounds: &Bounds) -> Vec<Id> {
+		AabbTree::query_aabb(self, bounds)
+	}
+	fn raycast(&self, origin: &Vec2, direction: &Vec2, max_distance: Geo, visit: &mut dyn FnMut(&[Id], Geo) -> bool) {
+		self.traverse_ray(origin, direction, max_distance, |ids, exit| visit(ids, exit));
+	}
+}
@@ -1,9 +1,12 @@
-use crate::{Vec2, Geo, Id, Bounds};
+use crate::{Vec2, Geo, Id, Bounds, Shape};
 
 mod body_options;
 pub use body_options::BodyOptions;
 
-use crate::grid::GridPairId;
+mod edge;
+pub use edge::{Edge, EdgeChain, EdgeChainOptions};
+
+use crate::grid::{GridBounds, GridPairId};
 
 pub struct Body {
 	pub id: Id,
@@ -14,6 +17,10 @@ pub struct Body {
 	pub angle: Geo,
 	pub velocity: Vec2,
 	pub angular_velocity: Geo,
+
+	// Accumulated between steps, cleared by physics::apply_forces
+	pub force: Vec2,
+	pub torque: Geo,
 	
 	// Calculated
 	pub axes: Vec<Vec2>,
@@ -27,9 +34,22 @@ pub struct Body {
 	pub is_static: bool,
 	pub friction: Geo,
 	pub restitution: Geo,
+	pub linear_damping: Geo, // fraction of velocity lost per second is 1 - e^-linear_damping
+	pub angular_damping: Geo,
+	pub gravity_scale: Geo, // 0 floats, negative rises
+	pub gravity: Option<Vec2>, // used instead of World::gravity if set
+	pub fixed_rotation: bool,
+	pub lock_x: bool,
+	pub lock_y: bool,
+	pub bullet: bool,
+
+	// Set for zero thickness line segments, whose vertices are just the two ends. See Body::edge
+	pub edge: Option<Edge>,
 
 	// Used by other parts of engine
 	pub grid_spaces: Vec<GridPairId>,
+	pub grid_bounds: Option<GridBounds>, // range of buckets grid_spaces covers, None if not in the grid
+	pub ccd_carry: Geo, // fraction of a step a bullet ran out of substeps to move, moved at the start of its next step. See physics::ccd
 }
 
 static mut GLOBAL_BODY_ID : Id = 0;
@@ -41,7 +61,9 @@ impl Body {
 
 	pub fn new(vertices: Vec<Vec2>, position: Vec2, options: BodyOptions) -> Body {
 		assert!(vertices.len() >= 3); // There should be at least 3 vertices for a valid body
-
+		Body::from_vertices(vertices, position, options)
+	}
+	fn from_vertices(vertices: Vec<Vec2>, position: Vec2, options: BodyOptions) -> Body {
 		let id: Id;
 		unsafe {
 			id = GLOBAL_BODY_ID;
@@ -60,10 +82,23 @@ impl Body {
 			angle: 0.0,
 			angular_velocity: 0.0,
 
+			force: Vec2::zero(),
+			torque: 0.0,
+
 			mass: options.mass,
 			is_static: options.is_static,
 			friction: options.friction,
 			restitution: options.restitution,
+			linear_damping: options.linear_damping,
+			angular_damping: options.angular_damping,
+			gravity_scale: options.gravity_scale,
+			gravity: options.gravity,
+			fixed_rotation: options.fixed_rotation,
+			lock_x: options.lock_x,
+			lock_y: options.lock_y,
+			bullet: options.bullet,
+
+			edge: None,
 			
 			inverse_mass: 1.0 / options.mass,
 			inertia: 1.0,
@@ -71,6 +106,8 @@ impl Body {
 			bounds: bounds,
 
 			grid_spaces: Vec::new(),
+			grid_bounds: None,
+			ccd_carry: 0.0,
 		};
 
 		body.update_inertia();
@@ -80,25 +117,17 @@ impl Body {
 		body
 	}
 	pub fn rectangle(width: Geo, height: Geo, position: Vec2, options: BodyOptions) -> Body {
-		let half_width = width / 2.0;
-		let half_height = height / 2.0;
-		let vertices = vec![
-			Vec2::new(-half_width, -half_height), // top left
-			Vec2::new( half_width, -half_height), // top right
-			Vec2::new( half_width,  half_height), // bottom right
-			Vec2::new(-half_width,  half_height), // bottom left
-		];
-		Body::new(vertices, position, options)
+		Body::new(Shape::rectangle(width, height).vertices, position, options)
 	}
 	pub fn circle(radius: Geo, position: Vec2, options: BodyOptions) -> Body {
-		let mut vertices = Vec::new();
-		let vertice_count = (radius.powf(0.333) * 8.0).round() as u32;
-		
-		let start_angle = crate::TAU * 2.0 / vertice_count as Geo;
-		for i in 0..vertice_count {
-			vertices.push(Vec2::new((start_angle * i as Geo + start_angle / 2.0).cos() * radius, (start_angle * i as Geo + start_angle / 2.0).sin() * radius));
-		}
-		Body::new(vertices, position, options)
+		Body::new(Shape::circle(radius).vertices, position, options)
+	}
+	// Zero thickness line segment from start to end, always static. Positioned at its middle
+	pub fn edge(start: Vec2, end: Vec2, options: BodyOptions) -> Body {
+		let center = (start + end) / 2.0;
+		let mut body = Body::from_vertices(vec![start - center, end - center], center, options.is_static(true));
+		body.edge = Some(Edge::default());
+		body
 	}
 
 	// Helper methods
@@ -158,6 +187,28 @@ impl Body {
 	pub fn get_inertia(&self) -> Geo { self.inertia }
 	pub fn get_inverse_inertia(&self) -> Geo { self.inverse_inertia }
 	pub fn get_bounds(&self) -> &Bounds { &self.bounds } 
+	pub fn is_edge(&self) -> bool { self.edge.is_some() }
+	// World space ghost vertices before and after an edge, see Edge
+	pub fn get_ghost_vertices(&self) -> (Option<Vec2>, Option<Vec2>) {
+		let Some(edge) = &self.edge else { return (None, None) };
+		let to_world = |vertex: Vec2| vertex.rotate(self.angle) + self.position;
+		(edge.ghost_prev.map(to_world), edge.ghost_next.map(to_world))
+	}
+	// Inverse mass along each axis, with locked axes treated as infinitely heavy
+	pub fn get_axis_inverse_mass(&self) -> Vec2 {
+		Vec2::new(
+			if self.lock_x { 0.0 } else { self.inverse_mass },
+			if self.lock_y { 0.0 } else { self.inverse_mass },
+		)
+	}
+	// Inverse inertia, with fixed rotation treated as infinite inertia
+	pub fn get_rotational_inverse_inertia(&self) -> Geo {
+		if self.fixed_rotation { 0.0 } else { self.inverse_inertia }
+	}
+	// Gravity acceleration this body feels in the given world gravity
+	pub fn get_gravity(&self, world_gravity: &Vec2) -> Vec2 {
+		self.gravity.unwrap_or(*world_gravity) * self.gravity_scale
+	}
 
 	//
 	// setters
@@ -207,12 +258,49 @@ impl Body {
 		self.axes = Body::get_axes(&self.vertices);
 		self.bounds.update_from_vertices(&self.vertices);
 	}
+	// Ghost vertices of an edge, in world space. Does nothing if the body isn't an edge
+	pub fn set_ghost_vertices(&mut self, prev: Option<Vec2>, next: Option<Vec2>) {
+		let to_local = |vertex: Vec2| (vertex - self.position).rotate(-self.angle);
+		let ghost_prev = prev.map(to_local);
+		let ghost_next = next.map(to_local);
+		let Some(edge) = &mut self.edge else { return };
+		edge.ghost_prev = ghost_prev;
+		edge.ghost_next = ghost_next;
+	}
 	pub fn apply_angular_velocity(&mut self, force: Geo) {
 		self.angular_velocity += force;
 	}
 
+	// forces; applied over the next step, taking mass into account
+	pub fn apply_force(&mut self, force: &Vec2) {
+		self.force += force;
+	}
+	pub fn apply_force_at_point(&mut self, force: &Vec2, point: &Vec2) {
+		self.force += force;
+		self.torque += (point - self.position).cross(force);
+	}
+	pub fn apply_torque(&mut self, torque: Geo) {
+		self.torque += torque;
+	}
+	pub fn clear_forces(&mut self) {
+		self.force = Vec2::zero();
+		self.torque = 0.0;
+	}
+
+	// impulses; instant changes in momentum
+	pub fn apply_impulse(&mut self, impulse: &Vec2) {
+		if self.is_static { return; }
+		self.velocity += impulse * self.get_axis_inverse_mass();
+	}
+	pub fn apply_impulse_at_point(&mut self, impulse: &Vec2, point: &Vec2) {
+		if self.is_static { return; }
+		self.velocity += impulse * self.get_axis_inverse_mass();
+		self.angular_velocity += (point - self.position).cross(impulse) * self.get_rotational_inverse_inertia();
+	}
+
 	// physics helper methods
 	pub fn contains_point(&self, point: &Vec2) -> bool {
+		if self.is_edge() { return false; } // no area to be inside of
 		let vertices = &self.vertices;
 		for i in 0..vertices.len() {
 			let cur_vertex = &vertices[i];
@@ -228,6 +316,57 @@ impl Body {
 
 		true
 	}
+	// Distance along the ray and outward surface normal where it enters the body, if it does within max_distance
+	// Rays starting inside the body don't hit it. direction must be normalized
+	pub fn raycast(&self, origin: &Vec2, direction: &Vec2, max_distance: Geo) -> Option<(Geo, Vec2)> {
+		if self.is_edge() { return self.raycast_edge(origin, direction, max_distance); }
+
+		// Clip the ray against every edge's half plane (Cyrus-Beck)
+		let vertices = &self.vertices;
+		let mut enter = Geo::NEG_INFINITY;
+		let mut exit = max_distance;
+		let mut normal = Vec2::zero();
+		for (i, cur_vertex) in vertices.iter().enumerate() {
+			let next_vertex = &vertices[(i + 1) % vertices.len()];
+			let edge_normal = (next_vertex - cur_vertex).normal(); // outward, same as in contains_point
+			let distance = edge_normal.dot(&(cur_vertex - origin)); // positive if origin is inside this edge
+			let speed = edge_normal.dot(direction);
+
+			if speed == 0.0 { // parallel to edge
+				if distance < 0.0 { return None; }
+				continue;
+			}
+			let t = distance / speed;
+			if speed < 0.0 { // entering
+				if t > enter {
+					enter = t;
+					normal = edge_normal;
+				}
+			}
+			else {
+				exit = exit.min(t);
+			}
+			if enter > exit { return None; }
+		}
+
+		if enter < 0.0 { return None; } // starts inside
+		Some((enter, normal.normalize()))
+	}
+	// Edges can be hit from either side; the normal faces back toward the ray
+	fn raycast_edge(&self, origin: &Vec2, direction: &Vec2, max_distance: Geo) -> Option<(Geo, Vec2)> {
+		let start = self.vertices[0];
+		let edge = self.vertices[1] - start;
+		let denominator = direction.cross(&edge);
+		if denominator == 0.0 { return None; } // parallel
+
+		let to_start = start - origin;
+		let distance = to_start.cross(&edge) / denominator;
+		let along = to_start.cross(direction) / denominator; // fraction of the way from start to end
+		if distance < 0.0 || distance > max_distance || !(0.0..=1.0).contains(&along) { return None; }
+
+		let normal = edge.normal().normalize();
+		Some((distance, if normal.dot(direction) > 0.0 { -normal } else { normal }))
+	}
 	pub fn get_support(&self, direction: &Vec2) -> usize {
 		let vertices = &self.vertices;
 		let mut farthest_dist: Geo = Geo::MIN; // farthest distance in direction
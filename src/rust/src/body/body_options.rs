@@ -1,14 +1,22 @@
-use crate::Geo;
+use crate::{Geo, Vec2};
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::JsValue;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct BodyOptions {
 	pub is_static: bool,
 	pub mass: Geo,
 	pub restitution: Geo,
 	pub friction: Geo,
+	pub linear_damping: Geo,
+	pub angular_damping: Geo,
+	pub gravity_scale: Geo,
+	pub gravity: Option<Vec2>, // used instead of World::gravity if set
+	pub fixed_rotation: bool,
+	pub lock_x: bool, // can't move along the x axis
+	pub lock_y: bool, // can't move along the y axis
+	pub bullet: bool, // fast body that's swept to stop it tunneling through others, see physics::ccd
 }
 
 impl BodyOptions {
@@ -18,6 +26,14 @@ impl BodyOptions {
 			mass: 1.0,
 			restitution: 0.3,
 			friction: 0.2,
+			linear_damping: 0.0,
+			angular_damping: 0.0,
+			gravity_scale: 1.0,
+			gravity: None,
+			fixed_rotation: false,
+			lock_x: false,
+			lock_y: false,
+			bullet: false,
 		}
 	}
 	pub fn is_static(mut self, s: bool) -> Self {
@@ -36,6 +52,38 @@ impl BodyOptions {
 		self.friction = f;
 		self
 	}
+	pub fn linear_damping(mut self, d: Geo) -> Self {
+		self.linear_damping = d;
+		self
+	}
+	pub fn angular_damping(mut self, d: Geo) -> Self {
+		self.angular_damping = d;
+		self
+	}
+	pub fn gravity_scale(mut self, s: Geo) -> Self {
+		self.gravity_scale = s;
+		self
+	}
+	pub fn gravity(mut self, g: Option<Vec2>) -> Self {
+		self.gravity = g;
+		self
+	}
+	pub fn fixed_rotation(mut self, f: bool) -> Self {
+		self.fixed_rotation = f;
+		self
+	}
+	pub fn lock_x(mut self, l: bool) -> Self {
+		self.lock_x = l;
+		self
+	}
+	pub fn lock_y(mut self, l: bool) -> Self {
+		self.lock_y = l;
+		self
+	}
+	pub fn bullet(mut self, b: bool) -> Self {
+		self.bullet = b;
+		self
+	}
 }
 
 impl Default for BodyOptions {
@@ -45,6 +93,14 @@ impl Default for BodyOptions {
 			mass: 1.0,
 			restitution: 0.2,
 			friction: 0.2,
+			linear_damping: 0.0,
+			angular_damping: 0.0,
+			gravity_scale: 1.0,
+			gravity: None,
+			fixed_rotation: false,
+			lock_x: false,
+			lock_y: false,
+			bullet: false,
 		}
 	}
 }
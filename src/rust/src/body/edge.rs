@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsValue;
+
+use crate::{Body, BodyMap, BodyOptions, Id, Vec2};
+
+// Extra data for edge bodies. Ghost vertices are the vertices before and after the edge in a chain, relative to the body's position, unrotated
+// They're never collided with, only used so bodies sliding over the joins between edges don't catch on them, see collisions::create_edge_manifold
+#[derive(Clone, Default)]
+pub struct Edge {
+	pub ghost_prev: Option<Vec2>,
+	pub ghost_next: Option<Vec2>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct EdgeChainOptions {
+	pub closed: bool, // whether the last vertex connects back to the first
+	pub body: BodyOptions, // used for every edge. Always made static
+}
+
+impl Default for EdgeChainOptions {
+	fn default() -> Self {
+		Self {
+			closed: false,
+			body: BodyOptions::new().is_static(true),
+		}
+	}
+}
+
+impl From<JsValue> for EdgeChainOptions {
+	fn from(value: JsValue) -> EdgeChainOptions {
+		if value.is_null_or_undefined() {
+			return EdgeChainOptions::default();
+		}
+
+		serde_wasm_bindgen::from_value(value)
+			.unwrap_or_else(|error| {
+				web_sys::console::warn_1(&format!("Failed to parse EdgeChainOptions: {}. Using defaults.", error).into());
+				EdgeChainOptions::default()
+			})
+	}
+}
+
+// Ids of the edges created by EdgeChain::create, in order along the chain
+#[derive(Serialize)]
+pub struct EdgeChain {
+	pub bodies: Vec<Id>,
+}
+
+impl EdgeChain {
+	// Creates an edge between every two consecutive vertices, each knowing its neighbours as ghost vertices. Like any other body, they aren't in the world until they're added
+	pub fn create(bodies: &mut BodyMap, vertices: &[Vec2], options: EdgeChainOptions) -> EdgeChain {
+		let mut chain = EdgeChain {
+			bodies: Vec::new(),
+		};
+		let len = vertices.len();
+		let closed = options.closed && len >= 3;
+		if len < 2 { return chain } // no edges to make
+
+		// Vertex at index i, wrapping around if the chain is closed
+		let get_vertex = |i: isize| -> Option<Vec2> {
+			if closed { Some(vertices[i.rem_euclid(len as isize) as usize]) }
+			else if i >= 0 && (i as usize) < len { Some(vertices[i as usize]) }
+			else { None }
+		};
+
+		let edges = if closed { len } else { len - 1 };
+		for i in 0..edges as isize {
+			let start = get_vertex(i).unwrap(); // unwrap ok, every edge's own vertices exist
+			let end = get_vertex(i + 1).unwrap();
+			let mut body = Body::edge(start, end, options.body.clone());
+			body.set_ghost_vertices(get_vertex(i - 1), get_vertex(i + 2));
+			chain.bodies.push(body.id);
+			bodies.insert(body.id, body);
+		}
+
+		chain
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EngineOptions, Geo, World, physics};
+
+	// Slides a frictionless box along a flat floor made of short edges, pressed down hard so it would catch on any seam
+	// Returns the box's final x, and the largest angle it tipped to
+	fn slide(ghost_vertices: bool) -> (Geo, Geo) {
+		let mut world = World::from_options(&EngineOptions::new().bucket_size(64.0));
+		world.gravity = Vec2::new(0.0, 6000.0);
+		let mut bodies = BodyMap::default();
+		let floor_options = BodyOptions::new().is_static(true).friction(0.0);
+		let vertices: Vec<Vec2> = (0..=40).map(|i| Vec2::new(i as Geo * 32.0, 0.0)).collect();
+		if ghost_vertices {
+			let chain = EdgeChain::create(&mut bodies, &vertices, EdgeChainOptions { closed: false, body: floor_options });
+			for id in chain.bodies {
+				world.add_body(bodies.get_mut(&id).unwrap());
+			}
+		}
+		else {
+			for pair in vertices.windows(2) {
+				let mut edge = Body::edge(pair[0], pair[1], floor_options.clone());
+				world.add_body(&mut edge);
+				bodies.insert(edge.id, edge);
+			}
+		}
+
+		let mut body = Body::rectangle(30.0, 30.0, Vec2::new(40.0, -15.5), BodyOptions::new().friction(0.0));
+		body.set_velocity(Vec2::new(600.0, 0.0));
+		let id = body.id;
+		world.add_body(&mut body);
+		bodies.insert(id, body);
+
+		let mut max_angle: Geo = 0.0;
+		for _ in 0..120 {
+			physics::update(&mut world, &mut bodies, 1.0 / 60.0);
+			max_angle = max_angle.max(bodies[&id].get_angle().abs());
+		}
+		(bodies[&id].get_position().x, max_angle)
+	}
+
+	#[test]
+	fn ghost_vertices_stop_snagging() {
+		let (x, max_angle) = slide(true);
+		assert!(x > 1000.0, "box should slide along the chain, stopped at x {}", x);
+		assert!(max_angle < 0.05, "box shouldn't tip over on the chain, tipped to {}", max_angle);
+
+		let (x, max_angle) = slide(false);
+		assert!(x < 500.0 || max_angle > 0.5, "box should catch on a seam between loose edges, got to x {} tipped to {}", x, max_angle);
+	}
+}
@@ -1,22 +1,28 @@
 use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::{Vec2};
+use crate::{Geo, Vec2};
 
 #[wasm_bindgen]
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Bounds {
 	pub min: Vec2,
 	pub max: Vec2
 }
 
+#[wasm_bindgen]
 impl Bounds {
 	// Create a new Bounds with a given min and max
+	#[wasm_bindgen(constructor)]
 	pub fn new(min: Vec2, max: Vec2) -> Self {
 		Self {
 			min: min,
 			max: max,
 		}
 	}
+}
+
+impl Bounds {
 
 	// Create a new Bounds with zeroed out min and max
 	pub fn empty() -> Self {
@@ -26,7 +32,7 @@ impl Bounds {
 		}
 	}
 
-	pub fn from_vertices(vertices: &Vec<Vec2>) -> Self {
+	pub fn from_vertices(vertices: &[Vec2]) -> Self {
 		let mut bounds = Bounds::empty();
 		for vertex in vertices {
 			bounds.expand_to(vertex);
@@ -52,7 +58,7 @@ impl Bounds {
 	}
 
 	// Updates the current bounds to cover the given vertices
-	pub fn update_from_vertices(&mut self, vertices: &Vec<Vec2>) {
+	pub fn update_from_vertices(&mut self, vertices: &[Vec2]) {
 		if vertices.len() <= 0 {
 			panic!("Bounds::update_from_vertices: vertices.len() must be > 0")
 		}
@@ -76,4 +82,52 @@ impl Bounds {
 		&& self.min.y <= other_bounds.max.y
 		&& self.max.y >= other_bounds.min.y
 	}
+
+	// Determines if other_bounds is completely inside the bounds
+	pub fn contains(&self, other_bounds: &Bounds) -> bool {
+		   self.min.x <= other_bounds.min.x
+		&& self.max.x >= other_bounds.max.x
+		&& self.min.y <= other_bounds.min.y
+		&& self.max.y >= other_bounds.max.y
+	}
+
+	// Smallest bounds covering both bounds
+	pub fn union(&self, other_bounds: &Bounds) -> Bounds {
+		Bounds {
+			min: self.min.min(&other_bounds.min),
+			max: self.max.max(&other_bounds.max),
+		}
+	}
+
+	// Bounds grown by margin on every side
+	pub fn fattened(&self, margin: Geo) -> Bounds {
+		let margin = Vec2::new(margin, margin);
+		Bounds {
+			min: self.min - margin,
+			max: self.max + margin,
+		}
+	}
+
+	pub fn perimeter(&self) -> Geo {
+		2.0 * ((self.max.x - self.min.x) + (self.max.y - self.min.y))
+	}
+
+	// Slab test: distances along the ray from origin where it enters and leaves the bounds, if it does within max_distance
+	// Starts at 0 if origin is inside
+	pub fn raycast(&self, origin: &Vec2, direction: &Vec2, max_distance: Geo) -> Option<(Geo, Geo)> {
+		let mut enter: Geo = 0.0;
+		let mut exit = max_distance;
+		for (origin, direction, min, max) in [(origin.x, direction.x, self.min.x, self.max.x), (origin.y, direction.y, self.min.y, self.max.y)] {
+			if direction == 0.0 {
+				if origin < min || origin > max { return None; }
+				continue;
+			}
+			let t1 = (min - origin) / direction;
+			let t2 = (max - origin) / direction;
+			enter = enter.max(t1.min(t2));
+			exit = exit.min(t1.max(t2));
+			if enter > exit { return None; }
+		}
+		Some((enter, exit))
+	}
 }
\ No newline at end of file
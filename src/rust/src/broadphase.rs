@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{AabbTree, Body, BodyMap, Bounds, EngineOptions, Geo, Grid, Id, Vec2};
+
+mod brute_force;
+pub use brute_force::BruteForce;
+
+mod sweep_and_prune;
+pub use sweep_and_prune::SweepAndPrune;
+
+// Finds pairs of bodies that might be touching, and bodies that might be hit by queries. The World owns one, picked with BroadphaseKind
+pub trait Broadphase {
+	fn insert(&mut self, body: &mut Body);
+	fn remove(&mut self, body: &mut Body);
+	// Called every step for every dynamic body in the world, after it moves
+	fn update(&mut self, body: &mut Body);
+	// Adds pairs of bodies whose bounds overlap to pairs, each pair once. Static bodies are never paired with each other
+	fn pairs(&self, bodies: &BodyMap, pairs: &mut Vec<(Id, Id)>);
+	// Ids of bodies that might overlap bounds, without duplicates
+	fn query_aabb(&self, bounds: &Bounds) -> Vec<Id>;
+	// Calls visit with groups of bodies the ray might hit until it returns true. direction must be normalized
+	// visit also gets the distance the ray has been covered up to; bodies in later groups are only hit past it
+	fn raycast(&self, origin: &Vec2, direction: &Vec2, max_distance: Geo, visit: &mut dyn FnMut(&[Id], Geo) -> bool);
+	// The grid, if this is one. Used for bucket sizes and debug rendering
+	fn as_grid(&self) -> Option<&Grid> { None }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BroadphaseKind {
+	Grid, // uniform grid, best when bodies are all around the same size
+	Tree, // dynamic AABB tree, best when sizes vary a lot
+	SweepAndPrune, // sorted along the x axis, best for wide, mostly horizontal worlds
+	BruteForce, // checks every pair; only useful for small worlds and testing the others
+}
+
+impl BroadphaseKind {
+	pub fn create(options: &EngineOptions) -> Box<dyn Broadphase> {
+		match options.broadphase {
+			BroadphaseKind::Grid => match &options.bounds {
+				Some(bounds) => Box::new(Grid::with_bounds(options.bucket_size, bounds)),
+				None => Box::new(Grid::new(options.bucket_size)),
+			},
+			BroadphaseKind::Tree => Box::new(AabbTree::new(options.tree_margin)),
+			BroadphaseKind::SweepAndPrune => Box::new(SweepAndPrune::new()),
+			BroadphaseKind::BruteForce => Box::new(BruteForce::new()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashSet;
+
+	use crate::{Body, BodyMap, BodyOptions, BroadphaseKind, EngineOptions, Geo, Id, Vec2, World};
+
+	// Small deterministic generator, so failures can be reproduced
+	struct Random(u32);
+
+	impl Random {
+		fn next(&mut self) -> Geo {
+			self.0 = self.0.wrapping_mul(1664525).wrapping_add(1013904223);
+			(self.0 >> 8) as Geo / (1 << 24) as Geo
+		}
+		fn range(&mut self, min: Geo, max: Geo) -> Geo {
+			min + self.next() * (max - min)
+		}
+	}
+
+	fn sorted_pairs(world: &World, bodies: &BodyMap) -> Vec<(Id, Id)> {
+		let mut pairs = Vec::new();
+		world.find_pairs(bodies, &mut pairs);
+		let mut sorted: Vec<(Id, Id)> = pairs.iter().map(|(a, b)| (*a.min(b), *a.max(b))).collect();
+		sorted.sort();
+		let unique: HashSet<(Id, Id)> = sorted.iter().copied().collect();
+		assert_eq!(unique.len(), sorted.len(), "pairs should only be reported once");
+		sorted
+	}
+
+	#[test]
+	fn broadphases_match_brute_force() {
+		let kinds = [(BroadphaseKind::Grid, "grid"), (BroadphaseKind::Tree, "tree"), (BroadphaseKind::SweepAndPrune, "sweep and prune")];
+		let mut random = Random(12345);
+		let mut bodies = BodyMap::default();
+		let mut reference = World::from_options(&EngineOptions::new().broadphase(BroadphaseKind::BruteForce));
+		let mut worlds: Vec<World> = kinds.iter().map(|(kind, _)| World::from_options(&EngineOptions::new().broadphase(*kind))).collect();
+
+		let mut ids = Vec::new();
+		for i in 0..80 {
+			let size = Vec2::new(random.range(5.0, 120.0), random.range(5.0, 120.0));
+			let position = Vec2::new(random.range(-500.0, 500.0), random.range(-500.0, 500.0));
+			let mut body = Body::rectangle(size.x, size.y, position, BodyOptions::new().is_static(i % 5 == 0));
+			reference.add_body(&mut body);
+			for world in worlds.iter_mut() {
+				world.add_body(&mut body);
+			}
+			ids.push(body.id);
+			bodies.insert(body.id, body);
+		}
+
+		let mut removed: Vec<Id> = Vec::new();
+		for step in 0..60 {
+			for id in ids.iter() {
+				let body = bodies.get_mut(id).unwrap();
+				if body.is_static { continue; }
+				body.translate_position(Vec2::new(random.range(-40.0, 40.0), random.range(-40.0, 40.0)));
+			}
+			reference.update_broadphase(&mut bodies);
+			for world in worlds.iter_mut() {
+				world.update_broadphase(&mut bodies);
+			}
+
+			// Take a few bodies out, and put the ones taken out earlier back after they've moved
+			if step % 4 == 0 {
+				for id in removed.drain(..) {
+					let body = bodies.get_mut(&id).unwrap();
+					body.set_position(Vec2::new(random.range(-500.0, 500.0), random.range(-500.0, 500.0)));
+					reference.add_body(body);
+					for world in worlds.iter_mut() {
+						world.add_body(body);
+					}
+				}
+				for _ in 0..5 {
+					let id = ids[(random.next() * ids.len() as Geo) as usize % ids.len()];
+					if removed.contains(&id) { continue; }
+					let body = bodies.get_mut(&id).unwrap();
+					reference.remove_body(body);
+					for world in worlds.iter_mut() {
+						world.remove_body(body);
+					}
+					removed.push(id);
+				}
+			}
+
+			let expected = sorted_pairs(&reference, &bodies);
+			assert!(!expected.is_empty());
+			for ((_, name), world) in kinds.iter().zip(worlds.iter()) {
+				assert_eq!(sorted_pairs(world, &bodies), expected, "{} disagrees with brute force on step {}", name, step);
+			}
+		}
+	}
+}
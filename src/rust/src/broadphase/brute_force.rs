@@ -0,0 +1,48 @@
+use crate::{Body, BodyMap, Bounds, Broadphase, Geo, Id, Vec2};
+
+// Every body is a candidate for everything. Slow, but simple enough to check the other broadphases against
+pub struct BruteForce {
+	ids: Vec<Id>,
+}
+
+impl BruteForce {
+	pub fn new() -> Self {
+		Self { ids: Vec::new() }
+	}
+}
+
+impl Default for BruteForce {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Broadphase for BruteForce {
+	fn insert(&mut self, body: &mut Body) {
+		if !self.ids.contains(&body.id) {
+			self.ids.push(body.id);
+		}
+	}
+	fn remove(&mut self, body: &mut Body) {
+		self.ids.retain(|id| *id != body.id);
+	}
+	fn update(&mut self, _body: &mut Body) {}
+	fn pairs(&self, bodies: &BodyMap, pairs: &mut Vec<(Id, Id)>) {
+		for (i, body_a_id) in self.ids.iter().enumerate() {
+			let body_a = bodies.get(body_a_id).expect("failed to get body_a in BruteForce::pairs");
+			for body_b_id in self.ids[i + 1..].iter() {
+				let body_b = bodies.get(body_b_id).expect("failed to get body_b in BruteForce::pairs");
+				if body_a.is_static && body_b.is_static { continue; }
+				if body_a.bounds.overlaps_with(&body_b.bounds) {
+					pairs.push((*body_a_id, *body_b_id));
+				}
+			}
+		}
+	}
+	fn query_aabb(&self, _bounds: &Bounds) -> Vec<Id> {
+		self.ids.clone()
+	}
+	fn raycast(&self, _origin: &Vec2, _direction: &Vec2, max_distance: Geo, visit: &mut dyn FnMut(&[Id], Geo) -> bool) {
+		visit(&self.ids, max_distance);
+	}
+}
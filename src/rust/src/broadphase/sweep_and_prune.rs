@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use nohash_hasher::BuildNoHashHasher;
+
+use crate::{Body, BodyMap, Bounds, Broadphase, Geo, Id, Vec2};
+
+// Start or end of a body's bounds on the x axis
+struct Endpoint {
+	value: Geo,
+	body: Id,
+	is_min: bool,
+}
+
+impl Endpoint {
+	// Mins go before maxes at the same value, so bounds that only touch still count as overlapping, same as Bounds::overlaps_with
+	fn goes_before(&self, other: &Endpoint) -> bool {
+		self.value < other.value || (self.value == other.value && self.is_min && !other.is_min)
+	}
+}
+
+// Sort and sweep along the x axis. Best for worlds that are much wider than they are tall, like side scrollers
+// Endpoints stay sorted between steps, so moving a body only needs an insertion sort over the few endpoints it passed
+pub struct SweepAndPrune {
+	endpoints: Vec<Endpoint>, // sorted by value
+	indices: HashMap<Id, (usize, usize), BuildNoHashHasher<Id>>, // index of each body's min and max endpoint
+	bounds: HashMap<Id, Bounds, BuildNoHashHasher<Id>>, // bounds each body had when it was last inserted / updated
+}
+
+impl SweepAndPrune {
+	pub fn new() -> Self {
+		Self {
+			endpoints: Vec::new(),
+			indices: HashMap::with_hasher(BuildNoHashHasher::default()),
+			bounds: HashMap::with_hasher(BuildNoHashHasher::default()),
+		}
+	}
+
+	// Moves the endpoint at index into its sorted place, keeping indices up to date
+	fn sort_endpoint(&mut self, mut index: usize) {
+		while index > 0 && self.endpoints[index].goes_before(&self.endpoints[index - 1]) {
+			self.swap_endpoints(index, index - 1);
+			index -= 1;
+		}
+		while index + 1 < self.endpoints.len() && self.endpoints[index + 1].goes_before(&self.endpoints[index]) {
+			self.swap_endpoints(index, index + 1);
+			index += 1;
+		}
+	}
+	fn swap_endpoints(&mut self, a: usize, b: usize) {
+		self.endpoints.swap(a, b);
+		for index in [a, b] {
+			let endpoint = &self.endpoints[index];
+			let indices = self.indices.get_mut(&endpoint.body).unwrap(); // every endpoint's body has indices
+			if endpoint.is_min { indices.0 = index; } else { indices.1 = index; }
+		}
+	}
+	// Calls visit with every body whose x range overlaps min to max
+	fn visit_x_range<F>(&self, min: Geo, max: Geo, mut visit: F) where F: FnMut(Id) {
+		for endpoint in self.endpoints.iter() {
+			if endpoint.value > max { break; } // everything after starts past max
+			if !endpoint.is_min { continue; }
+			let body_bounds = &self.bounds[&endpoint.body];
+			if body_bounds.max.x >= min {
+				visit(endpoint.body);
+			}
+		}
+	}
+}
+
+impl Default for SweepAndPrune {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Broadphase for SweepAndPrune {
+	fn insert(&mut self, body: &mut Body) {
+		if self.indices.contains_key(&body.id) {
+			self.update(body);
+			return;
+		}
+
+		let len = self.endpoints.len();
+		self.endpoints.push(Endpoint { value: body.bounds.min.x, body: body.id, is_min: true });
+		self.endpoints.push(Endpoint { value: body.bounds.max.x, body: body.id, is_min: false });
+		self.indices.insert(body.id, (len, len + 1));
+		self.bounds.insert(body.id, body.bounds.clone());
+		self.sort_endpoint(len);
+		let max_index = self.indices[&body.id].1;
+		self.sort_endpoint(max_index);
+	}
+	fn remove(&mut self, body: &mut Body) {
+		if self.indices.remove(&body.id).is_none() { return; }
+		self.bounds.remove(&body.id);
+		self.endpoints.retain(|endpoint| endpoint.body != body.id);
+
+		// Everything after the removed endpoints shifted down
+		for (index, endpoint) in self.endpoints.iter().enumerate() {
+			let indices = self.indices.get_mut(&endpoint.body).unwrap();
+			if endpoint.is_min { indices.0 = index; } else { indices.1 = index; }
+		}
+	}
+	fn update(&mut self, body: &mut Body) {
+		let Some(&(min_index, max_index)) = self.indices.get(&body.id) else {
+			self.insert(body);
+			return;
+		};
+		self.bounds.insert(body.id, body.bounds.clone());
+
+		// Sort whichever end is leading first, so the other one doesn't get stuck behind it
+		let moving_right = body.bounds.max.x > self.endpoints[max_index].value;
+		self.endpoints[min_index].value = body.bounds.min.x;
+		self.endpoints[max_index].value = body.bounds.max.x;
+		for is_min in if moving_right { [false, true] } else { [true, false] } {
+			let indices = self.indices[&body.id];
+			self.sort_endpoint(if is_min { indices.0 } else { indices.1 });
+		}
+	}
+	fn pairs(&self, bodies: &BodyMap, pairs: &mut Vec<(Id, Id)>) {
+		// Sweep left to right, pairing each body with every body whose x range is still open
+		let mut active: Vec<Id> = Vec::new();
+		for endpoint in self.endpoints.iter() {
+			if !endpoint.is_min {
+				active.retain(|body_id| *body_id != endpoint.body);
+				continue;
+			}
+
+			let body = bodies.get(&endpoint.body).expect("failed to get body in SweepAndPrune::pairs");
+			for other_id in active.iter() {
+				let other = bodies.get(other_id).expect("failed to get other body in SweepAndPrune::pairs");
+				if body.is_static && other.is_static { continue; }
+				if body.bounds.overlaps_with(&other.bounds) {
+					pairs.push((*other_id, endpoint.body));
+				}
+			}
+			active.push(endpoint.body);
+		}
+	}
+	fn query_aabb(&self, bounds: &Bounds) -> Vec<Id> {
+		let mut ids = Vec::new();
+		self.visit_x_range(bounds.min.x, bounds.max.x, |body_id| {
+			if self.bounds[&body_id].overlaps_with(bounds) {
+				ids.push(body_id);
+			}
+		});
+		ids
+	}
+	fn raycast(&self, origin: &Vec2, direction: &Vec2, max_distance: Geo, visit: &mut dyn FnMut(&[Id], Geo) -> bool) {
+		// Everything in the box around the ray. No order, so the ray is only covered at max_distance
+		let end = origin + direction * max_distance;
+		let ray_bounds = Bounds::new(origin.min(&end), origin.max(&end));
+		let ids = self.query_aabb(&ray_bounds);
+		visit(&ids, max_distance);
+	}
+}
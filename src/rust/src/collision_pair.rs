@@ -27,6 +27,14 @@ pub struct CollisionPair {
 
 	pub friction: Geo,
 	pub restitution: Geo,
+
+	// Used for collision events
+	pub start_frame: Frame, // first frame of this run of consecutive frames touching
+	pub approach_speed: Geo, // how fast the bodies were moving into each other along the normal when found
+	pub impulse: Geo, // total normal impulse the solver applied this step
+
+	// Bodies aren't touching yet, but might this step. depth is minus the gap between them, see collisions::create_speculative_manifold
+	pub speculative: bool,
 }
 
 impl CollisionPair {
@@ -41,6 +49,10 @@ impl CollisionPair {
 			y * y + x
 		}
 	}
+	// Order independent pair id, for looking up a pair regardless of which body is the reference
+	pub fn key(x: Id, y: Id) -> PairId {
+		CollisionPair::pair_id(x.min(y), x.max(y))
+	}
 	pub fn unpair_id(id: PairId) -> (Id, Id) {
 		let z = (id as f64).sqrt() as PairId; // we want id floor'd, so precision loss is correct
 		let l = id - z * z; // safety: z is sqrt'd, so squaring cannot overflow
@@ -49,6 +61,10 @@ impl CollisionPair {
 	pub fn is_valid(&self, frame: Frame) -> bool {
 		self.frame >= frame
 	}
+	// Whether the bodies started touching this frame
+	pub fn is_new(&self) -> bool {
+		self.start_frame == self.frame
+	}
 }
 impl std::hash::Hash for CollisionPair {
 	fn hash<H>(&self, state: &mut H) where H: std::hash::Hasher {
@@ -60,4 +76,10 @@ impl std::cmp::PartialEq for CollisionPair {
 		CollisionPair::pair_id(self.body_a, self.body_b) == CollisionPair::pair_id(other.body_a, other.body_b)
 	}
 }
-impl std::cmp::Eq for CollisionPair {}
\ No newline at end of file
+impl std::cmp::Eq for CollisionPair {}
+// A touching pair the pre-solve callback disabled, see World::disabled_pairs
+pub struct DisabledPair {
+	pub start_frame: Frame, // carried over to the pair once it's enabled again
+	pub frame: Frame, // last frame it was disabled
+	pub last_enabled: Option<CollisionPair>, // the pair the last frame it was enabled, if it was since it started touching. Used for its End event
+}
@@ -1,4 +1,7 @@
-use crate::{Body, Bounds, Geo, Id, Vec2, World, physics};
+use crate::{Body, Bounds, Chain, CollisionPair, EdgeChain, Event, Geo, Grid, Id, Joint, QueryFilter, ShapeDef, Tilemap, Vec2, World, grid::GridPairId, physics, query, world::PreSolve};
+
+mod engine_options;
+pub use engine_options::EngineOptions;
 use wasm_bindgen::prelude::*;
 use std::collections::HashMap;
 use nohash_hasher::BuildNoHashHasher;
@@ -20,12 +23,13 @@ pub struct Engine {
 }
 #[wasm_bindgen]
 impl Engine {
+	// options: { bucket_size, capacity, bounds }, or undefined for defaults. See EngineOptions
 	#[wasm_bindgen(constructor)]
-	pub fn new() -> Self {
-		let default_bucket_size = 100.0; // todo: let user change this
+	pub fn new(options: JsValue) -> Self {
+		let options: EngineOptions = options.into();
 		Self {
-			world: World::new(default_bucket_size),
-			bodies: HashMap::with_hasher(BuildNoHashHasher::default()),
+			world: World::from_options(&options),
+			bodies: HashMap::with_capacity_and_hasher(options.capacity, BuildNoHashHasher::default()),
 		}
 	}
 
@@ -42,6 +46,30 @@ impl Engine {
 		self.bodies.insert(id, body);
 		id
 	}
+	// Create a static, zero thickness line segment from start to end
+	pub fn body_create_edge(&mut self, start: Vec2, end: Vec2, options: JsValue) -> Id {
+		let body = Body::edge(start, end, options.into());
+		let id = body.id;
+		self.bodies.insert(id, body);
+		id
+	}
+	// Create an edge between every two consecutive vertices ([{ x, y }]), joined smoothly with ghost vertices
+	// options: { closed, body }. Returns { bodies } with every created id in order, to add to the world with world_add_body
+	pub fn body_create_edge_chain(&mut self, vertices: JsValue, options: JsValue) -> JsValue {
+		let vertices: Vec<Vec2> = serde_wasm_bindgen::from_value(vertices)
+			.unwrap_or_else(|error| {
+				web_sys::console::warn_1(&format!("Failed to parse edge chain vertices: {}.", error).into());
+				Vec::new()
+			});
+		let chain = EdgeChain::create(&mut self.bodies, &vertices, options.into());
+		serde_wasm_bindgen::to_value(&chain).unwrap()
+	}
+	// Create static bodies for the solid (non-zero) tiles of a tilemap, merged into rectangles
+	// tiles is row by row, columns tiles wide. Returns { bodies } with every created id, to add to the world with world_add_body
+	pub fn body_create_tilemap(&mut self, tiles: Vec<u8>, columns: usize, tile_size: Geo, options: JsValue) -> JsValue {
+		let tilemap = Tilemap::create(&mut self.bodies, &tiles, columns, tile_size, options.into());
+		serde_wasm_bindgen::to_value(&tilemap).unwrap()
+	}
 	pub fn body_set_position(&mut self, body_id: Id, position: Vec2) {
 		if !self.bodies.contains_key(&body_id) { return } // Body doesn't exist
 		self.bodies.get_mut(&body_id).unwrap().set_position(position);
@@ -62,10 +90,49 @@ impl Engine {
 		if !self.bodies.contains_key(&body_id) { return } // Body doesn't exist
 		self.bodies.get_mut(&body_id).unwrap().apply_angular_velocity(velocity);
 	}
+	pub fn body_apply_force(&mut self, body_id: Id, force: Vec2) {
+		if !self.bodies.contains_key(&body_id) { return } // Body doesn't exist
+		self.bodies.get_mut(&body_id).unwrap().apply_force(&force);
+	}
+	pub fn body_apply_force_at_point(&mut self, body_id: Id, force: Vec2, point: Vec2) {
+		if !self.bodies.contains_key(&body_id) { return } // Body doesn't exist
+		self.bodies.get_mut(&body_id).unwrap().apply_force_at_point(&force, &point);
+	}
+	pub fn body_apply_impulse(&mut self, body_id: Id, impulse: Vec2) {
+		if !self.bodies.contains_key(&body_id) { return } // Body doesn't exist
+		self.bodies.get_mut(&body_id).unwrap().apply_impulse(&impulse);
+	}
+	pub fn body_apply_impulse_at_point(&mut self, body_id: Id, impulse: Vec2, point: Vec2) {
+		if !self.bodies.contains_key(&body_id) { return } // Body doesn't exist
+		self.bodies.get_mut(&body_id).unwrap().apply_impulse_at_point(&impulse, &point);
+	}
+	pub fn body_apply_torque(&mut self, body_id: Id, torque: Geo) {
+		if !self.bodies.contains_key(&body_id) { return } // Body doesn't exist
+		self.bodies.get_mut(&body_id).unwrap().apply_torque(torque);
+	}
+	pub fn body_set_gravity_scale(&mut self, body_id: Id, gravity_scale: Geo) {
+		if !self.bodies.contains_key(&body_id) { return } // Body doesn't exist
+		self.bodies.get_mut(&body_id).unwrap().gravity_scale = gravity_scale;
+	}
+	// Bullets are swept along their velocity each step so they can't tunnel through thin bodies
+	pub fn body_set_bullet(&mut self, body_id: Id, bullet: bool) {
+		if !self.bodies.contains_key(&body_id) { return } // Body doesn't exist
+		self.bodies.get_mut(&body_id).unwrap().bullet = bullet;
+	}
+	// Give a body its own gravity instead of the world's. Pass undefined to go back to world gravity
+	pub fn body_set_gravity(&mut self, body_id: Id, gravity: Option<Vec2>) {
+		if !self.bodies.contains_key(&body_id) { return } // Body doesn't exist
+		self.bodies.get_mut(&body_id).unwrap().gravity = gravity;
+	}
 	pub fn body_translate_angle(&mut self, body_id: Id, angle: Geo) {
 		if !self.bodies.contains_key(&body_id) { return } // Body doesn't exist
 		self.bodies.get_mut(&body_id).unwrap().translate_angle(angle);
 	}
+	// Vertices before and after an edge, in world space, so bodies don't catch on the joins with its neighbours. Pass undefined for a free end
+	pub fn body_set_ghost_vertices(&mut self, body_id: Id, prev: Option<Vec2>, next: Option<Vec2>) {
+		if !self.bodies.contains_key(&body_id) { return } // Body doesn't exist
+		self.bodies.get_mut(&body_id).unwrap().set_ghost_vertices(prev, next);
+	}
 	pub fn body_set_angle(&mut self, body_id: Id, angle: Geo) {
 		if !self.bodies.contains_key(&body_id) { return } // Body doesn't exist
 		self.bodies.get_mut(&body_id).unwrap().set_angle(angle);
@@ -80,6 +147,66 @@ impl Engine {
 		self.bodies.get(&body_id).unwrap().get_bounds().clone()
 	}
 
+	//
+	// Joint methods
+	//
+
+	// Pin two bodies together at a world point. Adds the joint to the world
+	pub fn joint_create_revolute(&mut self, body_a: Id, body_b: Id, anchor: Vec2) -> Option<Id> {
+		let joint = Joint::revolute(self.bodies.get(&body_a)?, self.bodies.get(&body_b)?, anchor);
+		let id = joint.id;
+		self.world.add_joint(joint);
+		Some(id)
+	}
+	// Let body_b slide along a world direction through a world point, without rotating relative to body_a. Adds the joint to the world
+	pub fn joint_create_prismatic(&mut self, body_a: Id, body_b: Id, anchor: Vec2, axis: Vec2) -> Option<Id> {
+		let joint = Joint::prismatic(self.bodies.get(&body_a)?, self.bodies.get(&body_b)?, anchor, axis);
+		let id = joint.id;
+		self.world.add_joint(joint);
+		Some(id)
+	}
+	// Keep two world points on two bodies at most max_length apart. Adds the joint to the world
+	pub fn joint_create_rope(&mut self, body_a: Id, anchor_a: Vec2, body_b: Id, anchor_b: Vec2, max_length: Geo) -> Option<Id> {
+		let joint = Joint::rope(self.bodies.get(&body_a)?, anchor_a, self.bodies.get(&body_b)?, anchor_b, max_length);
+		let id = joint.id;
+		self.world.add_joint(joint);
+		Some(id)
+	}
+	// Hang two world points on two bodies from fixed world points ground_a and ground_b with one rope. Adds the joint to the world
+	#[allow(clippy::too_many_arguments)]
+	pub fn joint_create_pulley(&mut self, body_a: Id, anchor_a: Vec2, body_b: Id, anchor_b: Vec2, ground_a: Vec2, ground_b: Vec2, ratio: Geo) -> Option<Id> {
+		let joint = Joint::pulley(self.bodies.get(&body_a)?, anchor_a, self.bodies.get(&body_b)?, anchor_b, ground_a, ground_b, ratio);
+		let id = joint.id;
+		self.world.add_joint(joint);
+		Some(id)
+	}
+	// Couple two revolute or prismatic joints by ratio. Adds the joint to the world
+	pub fn joint_create_gear(&mut self, joint_a: Id, joint_b: Id, ratio: Geo) -> Option<Id> {
+		let joint = Joint::gear(self.world.joints.get(&joint_a)?, self.world.joints.get(&joint_b)?, &self.bodies, ratio)?;
+		let id = joint.id;
+		self.world.add_joint(joint);
+		Some(id)
+	}
+	// Create a chain of linked bodies between anchor_a on body_a and anchor_b on body_b. Returns { bodies, joints } with every created id
+	pub fn joint_create_chain(&mut self, body_a: Id, anchor_a: Vec2, body_b: Id, anchor_b: Vec2, options: JsValue) -> JsValue {
+		let chain = Chain::create(&mut self.world, &mut self.bodies, body_a, anchor_a, body_b, anchor_b, options.into());
+		serde_wasm_bindgen::to_value(&chain).unwrap()
+	}
+	pub fn joint_set_collide_connected(&mut self, joint_id: Id, collide_connected: bool) {
+		if !self.world.joints.contains_key(&joint_id) { return } // Joint doesn't exist
+		self.world.joints.get_mut(&joint_id).unwrap().collide_connected = collide_connected;
+	}
+	// Break the joint once its reaction force / torque over a step goes past these. Pass undefined to make it unbreakable
+	pub fn joint_set_break_threshold(&mut self, joint_id: Id, force: Option<Geo>, torque: Option<Geo>) {
+		if !self.world.joints.contains_key(&joint_id) { return } // Joint doesn't exist
+		let joint = self.world.joints.get_mut(&joint_id).unwrap();
+		joint.break_force = force;
+		joint.break_torque = torque;
+	}
+	pub fn joint_remove(&mut self, joint_id: Id) {
+		self.world.remove_joint(joint_id);
+	}
+
 	//
 	// World methods
 	//
@@ -96,6 +223,45 @@ impl Engine {
 		let body = self.bodies.get_mut(&body_id).unwrap(); // unwrap ok, already checked it exists
 		self.world.remove_body(body);
 	}
+	pub fn world_set_gravity(&mut self, gravity: Vec2) {
+		self.world.gravity = gravity;
+	}
+	pub fn world_get_gravity(&self) -> Vec2 {
+		self.world.gravity
+	}
+	// Set damping applied to every body in the world, on top of each body's own damping
+	pub fn world_set_air_drag(&mut self, air_drag: Geo) {
+		self.world.air_drag = air_drag;
+	}
+	// Rebuilds the broadphase grid with a new bucket size. Does nothing with other broadphases
+	pub fn world_set_bucket_size(&mut self, bucket_size: Geo) {
+		self.world.set_bucket_size(bucket_size, &mut self.bodies);
+	}
+	// undefined if the broadphase isn't a grid
+	pub fn world_get_bucket_size(&self) -> Option<Geo> {
+		self.world.broadphase.as_grid().map(|grid| grid.bucket_size)
+	}
+	// Bucket size that fits the bodies currently in the world, to pass to world_set_bucket_size. undefined if there are no bodies
+	pub fn world_suggest_bucket_size(&self) -> Option<Geo> {
+		let bodies: Vec<&Body> = self.world.bodies.iter().map(|body_id| self.bodies.get(body_id).unwrap()).collect();
+		Grid::suggest_bucket_size(&bodies)
+	}
+	// Whether bullets are swept against dynamic bodies too. Off by default, since it's slower and static walls are the usual problem
+	pub fn world_set_ccd_dynamic(&mut self, ccd_dynamic: bool) {
+		self.world.ccd_dynamic = ccd_dynamic;
+	}
+	// Speculative contacts stop fast bodies before they reach each other, which is cheaper than bullets but less exact
+	pub fn world_set_speculative_contacts(&mut self, speculative_contacts: bool) {
+		self.world.speculative_contacts = speculative_contacts;
+	}
+	// Whether steps queue collision events for drain_events. Off by default; once on, call drain_events regularly or the queue keeps growing
+	// Joint breaks are queued either way
+	pub fn world_set_record_events(&mut self, record_events: bool) {
+		self.world.record_events = record_events;
+		if !record_events {
+			self.world.events.retain(|event| matches!(event, Event::JointBreak { .. }));
+		}
+	}
 	// Get all bodies in the world
 	pub fn world_get_bodies(&self) -> JsValue {
 		let mut bodies: Vec<Id> = Vec::new();
@@ -106,29 +272,120 @@ impl Engine {
 	}
 	// Get all active collision pairs
 	pub fn world_get_collision_pairs(&self) -> JsValue {
-		// world.collision_pairs = HashMap<Id, CollisionPair>
+		// world.collision_pairs = HashMap<PairId, CollisionPair>
 		let mut pairs = Vec::new();
-		for pair in self.world.collision_pairs.iter() {
+		for pair in self.world.collision_pairs.values() {
 			pairs.push(pair.clone());
 		}
 		serde_wasm_bindgen::to_value(&pairs).unwrap()
 	}
 
+	// Get all joints in the world
+	pub fn world_get_joints(&self) -> JsValue {
+		let joints: Vec<&Joint> = self.world.joints.values().collect();
+		serde_wasm_bindgen::to_value(&joints).unwrap()
+	}
+
+	// Set a function (body_a, body_b, is_new, touching) => result called for every touching pair each step, before solving
+	// With speculative contacts on, it's also called with touching false for pairs that are about to touch, so they can pass through each other too
+	// Return false to let the bodies pass through each other this step, or { enabled, friction, restitution } to override any of them
+	// It can't call engine methods, since the engine is busy stepping. Those calls throw and the callback's result is ignored
+	// Pass undefined to remove it
+	pub fn world_set_pre_solve(&mut self, callback: Option<js_sys::Function>) {
+		self.world.pre_solve = callback.map(|callback| Box::new(move |pair: &mut CollisionPair| physics::run_pre_solve(&callback, pair)) as PreSolve);
+	}
+	// Get all events queued since the last call as a flat array, and clear the queue. See event.rs for the layout, or decode with Events.js
+	pub fn drain_events(&mut self) -> Vec<Geo> {
+		let mut out = Vec::new();
+		for event in self.world.events.drain(..) {
+			event.write(&mut out);
+		}
+		out
+	}
+
 	pub fn world_get_grid(&self) -> JsValue {
-		let value = self.world.get_buckets();
 		let obj = js_sys::Object::new();
+		let Some(value) = self.world.get_buckets() else { return obj.into() }; // no grid to show
 
-		for x in value.1.iter() {
+		// Body count per bucket, with both layers added together
+		let mut counts: HashMap<GridPairId, usize> = HashMap::new();
+		for (bucket_id, bucket) in value.1.iter().chain(value.2.iter()) {
+			*counts.entry(*bucket_id).or_insert(0) += bucket.len();
+		}
+		for x in counts.iter() {
 			js_sys::Reflect::set(
 				&obj,
 				&JsValue::from_f64(*x.0 as f64),
-				&JsValue::from_f64(x.1.len() as f64),
+				&JsValue::from_f64(*x.1 as f64),
 			).unwrap();
 		}
 
 		obj.into()
 	}
 
+	//
+	// Query methods
+	//
+
+	// First body hit by a ray, as { body, point, normal, distance, fraction }, or undefined if nothing was hit
+	// filter: { exclude: [body ids], include_static, include_dynamic }, or undefined to allow everything
+	pub fn raycast(&self, origin: Vec2, direction: Vec2, max_distance: Geo, filter: JsValue) -> JsValue {
+		match query::raycast(&self.world, &self.bodies, &origin, &direction, max_distance, &filter.into()) {
+			Some(hit) => serde_wasm_bindgen::to_value(&hit).unwrap(),
+			None => JsValue::UNDEFINED,
+		}
+	}
+	// Every body hit by a ray, closest first. See raycast
+	pub fn raycast_all(&self, origin: Vec2, direction: Vec2, max_distance: Geo, filter: JsValue) -> JsValue {
+		let hits = query::raycast_all(&self.world, &self.bodies, &origin, &direction, max_distance, &filter.into());
+		serde_wasm_bindgen::to_value(&hits).unwrap()
+	}
+
+	// Every body containing point. See raycast for filter
+	pub fn query_point(&self, point: Vec2, filter: JsValue) -> JsValue {
+		let bodies = query::query_point(&self.world, &self.bodies, &point, &filter.into());
+		serde_wasm_bindgen::to_value(&bodies).unwrap()
+	}
+	// Every body whose bounds overlap bounds. See raycast for filter
+	pub fn query_aabb(&self, bounds: &Bounds, filter: JsValue) -> JsValue {
+		let bodies = query::query_aabb(&self.world, &self.bodies, bounds, &filter.into());
+		serde_wasm_bindgen::to_value(&bodies).unwrap()
+	}
+
+	// Every body overlapping a shape placed at position and rotated by angle. See shape_cast for shape and raycast for filter
+	// Nothing is added to the world, so this is safe to call e.g. to check if a spawn point is free
+	pub fn overlap_shape(&self, shape: JsValue, position: Vec2, angle: Geo, filter: JsValue) -> JsValue {
+		let Some(shape_def) = ShapeDef::from_js(shape) else { return JsValue::UNDEFINED };
+		let Some(shape) = shape_def.to_shape(&self.bodies) else { return JsValue::UNDEFINED };
+		let mut filter: QueryFilter = filter.into();
+		filter.exclude.extend(shape_def.get_body());
+
+		let bodies = query::overlap_shape(&self.world, &self.bodies, &shape, &position, angle, &filter);
+		serde_wasm_bindgen::to_value(&bodies).unwrap()
+	}
+
+	// First body a shape touches while moving from position by translation, as { body, fraction, position, point, normal }, or undefined
+	// shape: { type: "rectangle", width, height }, { type: "circle", radius }, { type: "polygon", vertices: [{ x, y }] } or { type: "body", body: id }
+	// A body shape never hits the body it was copied from. See raycast for filter
+	pub fn shape_cast(&self, shape: JsValue, position: Vec2, angle: Geo, translation: Vec2, filter: JsValue) -> JsValue {
+		let Some(shape_def) = ShapeDef::from_js(shape) else { return JsValue::UNDEFINED };
+		let Some(shape) = shape_def.to_shape(&self.bodies) else { return JsValue::UNDEFINED };
+		let mut filter: QueryFilter = filter.into();
+		filter.exclude.extend(shape_def.get_body());
+
+		match query::shape_cast(&self.world, &self.bodies, &shape, &position, angle, &translation, &filter) {
+			Some(hit) => serde_wasm_bindgen::to_value(&hit).unwrap(),
+			None => JsValue::UNDEFINED,
+		}
+	}
+
+	// Distance between two bodies, as { distance, point_a, point_b, normal }, or undefined if either body doesn't exist
+	// point_a and point_b are the closest points on each body. If they overlap, distance is negative and the points are the deepest ones
+	pub fn body_distance(&self, body_a: Id, body_b: Id) -> JsValue {
+		let (Some(body_a), Some(body_b)) = (self.bodies.get(&body_a), self.bodies.get(&body_b)) else { return JsValue::UNDEFINED }; // Body doesn't exist
+		serde_wasm_bindgen::to_value(&query::body_distance(body_a, body_b)).unwrap()
+	}
+
 	// Update the physics
 	pub fn physics_update(&mut self, delta: Geo) {
 		physics::update(&mut self.world, &mut self.bodies, delta);
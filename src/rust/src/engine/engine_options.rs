@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsValue;
+
+use crate::{BroadphaseKind, Bounds, Geo, grid::BucketSize};
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct EngineOptions {
+	pub bucket_size: BucketSize, // width / height of each broadphase grid bucket. See Engine::world_suggest_bucket_size
+	pub capacity: usize, // number of bodies to allocate room for up front
+	pub bounds: Option<Bounds>, // area bodies are expected to stay in, used to allocate grid buckets up front
+	pub broadphase: BroadphaseKind,
+	pub tree_margin: Geo, // how far past their bounds bodies can move before they're moved in the AABB tree
+}
+
+impl EngineOptions {
+	pub fn new() -> EngineOptions {
+		EngineOptions::default()
+	}
+	pub fn bucket_size(mut self, s: BucketSize) -> Self {
+		self.bucket_size = s;
+		self
+	}
+	pub fn capacity(mut self, c: usize) -> Self {
+		self.capacity = c;
+		self
+	}
+	pub fn bounds(mut self, b: Option<Bounds>) -> Self {
+		self.bounds = b;
+		self
+	}
+	pub fn broadphase(mut self, b: BroadphaseKind) -> Self {
+		self.broadphase = b;
+		self
+	}
+	pub fn tree_margin(mut self, m: Geo) -> Self {
+		self.tree_margin = m;
+		self
+	}
+}
+
+impl Default for EngineOptions {
+	fn default() -> Self {
+		Self {
+			bucket_size: 100.0,
+			capacity: 0,
+			bounds: None,
+			broadphase: BroadphaseKind::Grid,
+			tree_margin: 10.0,
+		}
+	}
+}
+
+impl From<JsValue> for EngineOptions {
+	fn from(value: JsValue) -> EngineOptions {
+		if value.is_null_or_undefined() {
+			return EngineOptions::default();
+		}
+
+		let mut options: EngineOptions = serde_wasm_bindgen::from_value(value)
+			.unwrap_or_else(|error| {
+				web_sys::console::warn_1(&format!("Failed to parse EngineOptions: {}. Using defaults.", error).into());
+				EngineOptions::default()
+			});
+
+		// Same check as World::set_bucket_size; a grid with empty buckets would never finish inserting a body
+		if options.bucket_size <= 0.0 || !options.bucket_size.is_finite() {
+			let default = EngineOptions::default().bucket_size;
+			web_sys::console::warn_1(&format!("Invalid bucket size {}, using {}.", options.bucket_size, default).into());
+			options.bucket_size = default;
+		}
+		options
+	}
+}
@@ -0,0 +1,77 @@
+use crate::{CollisionPair, Geo, Id, Vec2};
+
+/*
+Events are queued in World::events during a step and drained by JS as one flat Float32Array (see Engine::drain_events)
+Collision events are only queued while World::record_events is on (Engine::world_set_record_events), since the queue is only cleared by draining it
+JointBreak events are always queued; they're rare, and the only way to find out a joint broke
+Each event starts with its type, followed by its fields:
+	JointBreak:                   [0, joint, body_a, body_b, force, torque]
+	CollisionBegin/Persist/End:   [1/2/3, body_a, body_b, normal.x, normal.y, approach_speed, impulse, contact_count, ...contacts as x, y]
+src/js/Events.js decodes this back into objects
+*/
+pub enum Event {
+	JointBreak { joint: Id, body_a: Id, body_b: Id, force: Geo, torque: Geo },
+	CollisionBegin(CollisionEvent),
+	CollisionPersist(CollisionEvent),
+	CollisionEnd(CollisionEvent), // uses the pair's data from the last frame it was touching
+}
+
+pub struct CollisionEvent {
+	pub body_a: Id,
+	pub body_b: Id,
+	pub normal: Vec2,
+	pub contacts: Vec<Vec2>,
+	pub approach_speed: Geo,
+	pub impulse: Geo,
+}
+
+impl CollisionEvent {
+	pub fn from_pair(pair: &CollisionPair) -> Self {
+		Self {
+			body_a: pair.body_a,
+			body_b: pair.body_b,
+			normal: pair.normal,
+			contacts: pair.contacts.iter().map(|contact| contact.vertex).collect(),
+			approach_speed: pair.approach_speed,
+			impulse: pair.impulse,
+		}
+	}
+	fn write(&self, out: &mut Vec<Geo>) {
+		out.extend_from_slice(&[
+			self.body_a as Geo,
+			self.body_b as Geo,
+			self.normal.x,
+			self.normal.y,
+			self.approach_speed,
+			self.impulse,
+			self.contacts.len() as Geo,
+		]);
+		for contact in self.contacts.iter() {
+			out.push(contact.x);
+			out.push(contact.y);
+		}
+	}
+}
+
+impl Event {
+	// Appends the event's compact form to out
+	pub fn write(&self, out: &mut Vec<Geo>) {
+		match self {
+			Event::JointBreak { joint, body_a, body_b, force, torque } => {
+				out.extend_from_slice(&[0.0, *joint as Geo, *body_a as Geo, *body_b as Geo, *force, *torque]);
+			},
+			Event::CollisionBegin(event) => {
+				out.push(1.0);
+				event.write(out);
+			},
+			Event::CollisionPersist(event) => {
+				out.push(2.0);
+				event.write(out);
+			},
+			Event::CollisionEnd(event) => {
+				out.push(3.0);
+				event.write(out);
+			},
+		}
+	}
+}
@@ -1,15 +1,20 @@
 use std::collections::{HashMap, hash_map::Iter};
 
-use crate::{Body, Geo, Id, Vec2};
+use nohash_hasher::BuildNoHashHasher;
+
+use crate::{Body, BodyMap, Bounds, Broadphase, CollisionPair, Geo, Id, PairId, Vec2};
 
 pub type GridPosition = i32; // grid positions
 pub type GridPairId = u64; // paired grid positions; should be u128, but that doesn't work with hasher :(
 pub type BucketSize = Geo;
 pub type GridHashMap = HashMap<GridPairId, Vec<Id>>;
 
+const MAX_RESERVED_BUCKETS: u64 = 65536; // so huge world bounds don't allocate a huge grid
+
 
 // watered-down implementations with correct types
-struct GridVec {
+#[derive(Clone, Copy, PartialEq)]
+pub struct GridVec {
 	x: GridPosition,
 	y: GridPosition
 }
@@ -18,7 +23,8 @@ impl GridVec {
 		Self { x, y }
 	}
 }
-struct GridBounds {
+#[derive(Clone, Copy, PartialEq)]
+pub struct GridBounds {
 	min: GridVec,
 	max: GridVec,
 }
@@ -26,21 +32,52 @@ impl GridBounds {
 	pub fn new(min: GridVec, max: GridVec) -> Self {
 		Self { min, max }
 	}
+	fn contains(&self, x: GridPosition, y: GridPosition) -> bool {
+		x >= self.min.x && x <= self.max.x && y >= self.min.y && y <= self.max.y
+	}
 }
 
 pub struct Grid {
 	pub bucket_size: BucketSize,
-	pub buckets: GridHashMap,
+	pub buckets: GridHashMap, // dynamic bodies
+	pub static_buckets: GridHashMap, // static bodies, kept apart so they're never paired with each other
+	// Every pair of bodies sharing at least one bucket, keyed by CollisionPair::key, with how many buckets they share
+	// Kept up to date as bodies enter and leave buckets, so finding pairs doesn't have to go through every bucket each step
+	shared: HashMap<PairId, (Id, Id, u32), BuildNoHashHasher<PairId>>,
 }
 
 impl Grid {
 	pub fn new(bucket_size: BucketSize) -> Self {
 		Self {
 			bucket_size,
-			buckets: GridHashMap::default()
+			buckets: GridHashMap::default(),
+			static_buckets: GridHashMap::default(),
+			shared: HashMap::with_hasher(BuildNoHashHasher::default()),
 		}
 	}
 
+	// Grid with room for every bucket in bounds, so it doesn't have to grow as bodies spread out
+	pub fn with_bounds(bucket_size: BucketSize, bounds: &Bounds) -> Self {
+		let mut grid = Grid::new(bucket_size);
+		let bucket_count = grid.count_buckets(bounds).min(MAX_RESERVED_BUCKETS);
+		grid.buckets.reserve(bucket_count as usize);
+		grid
+	}
+	// Twice the median body size, so most bodies only cover a few buckets. Static bodies are skipped since they're often huge walls / floors
+	// Returns None if there are no bodies to go off
+	pub fn suggest_bucket_size(bodies: &[&Body]) -> Option<BucketSize> {
+		let dynamic: Vec<&&Body> = bodies.iter().filter(|body| !body.is_static).collect();
+		let sample = if dynamic.is_empty() { bodies.iter().collect() } else { dynamic };
+		let mut sizes: Vec<Geo> = sample.iter()
+			.map(|body| (body.bounds.max.x - body.bounds.min.x).max(body.bounds.max.y - body.bounds.min.y))
+			.filter(|size| *size > 0.0)
+			.collect();
+		if sizes.is_empty() { return None; }
+
+		sizes.sort_by(|a, b| a.total_cmp(b));
+		Some(sizes[sizes.len() / 2] * 2.0)
+	}
+
 	pub fn get_bucket_iter(&self) -> Iter<'_, u64, Vec<u16>> {
 		self.buckets.iter()
 	}
@@ -72,24 +109,86 @@ impl Grid {
 	}
 
 	fn to_grid_space(&self, point: &Vec2) -> GridVec {
-		GridVec::new((point.x / self.bucket_size) as GridPosition, (point.y / self.bucket_size) as GridPosition)
+		// floor so every bucket is the same size; truncating would make the buckets around 0 twice as big
+		GridVec::new((point.x / self.bucket_size).floor() as GridPosition, (point.y / self.bucket_size).floor() as GridPosition)
+	}
+	// Ids of every body in the bucket containing position, dynamic and static
+	pub fn get_bucket_from_world_pos(&self, position: &Vec2) -> impl Iterator<Item = Id> + '_ {
+		let bucket_id = Grid::pair(&self.to_grid_space(position));
+		[self.buckets.get(&bucket_id), self.static_buckets.get(&bucket_id)].into_iter().flatten().flatten().copied()
+	}
+	// Ids of every body in the buckets bounds covers, without duplicates. Their bounds don't necessarily overlap
+	pub fn query_aabb(&self, bounds: &Bounds) -> Vec<Id> {
+		let grid_bounds = self.get_grid_bounds(bounds);
+
+		let mut ids = Vec::new();
+		for y in grid_bounds.min.y..=grid_bounds.max.y {
+			for x in grid_bounds.min.x..=grid_bounds.max.x {
+				let bucket_id = Grid::pair(&GridVec::new(x, y));
+				for bucket in [self.buckets.get(&bucket_id), self.static_buckets.get(&bucket_id)].into_iter().flatten() {
+					ids.extend_from_slice(bucket);
+				}
+			}
+		}
+		ids.sort_unstable();
+		ids.dedup();
+		ids
+	}
+	// Number of buckets (empty or not) bounds covers
+	pub fn count_buckets(&self, bounds: &Bounds) -> u64 {
+		let min = self.to_grid_space(&bounds.min);
+		let max = self.to_grid_space(&bounds.max);
+		(max.x as i64 - min.x as i64 + 1) as u64 * (max.y as i64 - min.y as i64 + 1) as u64
 	}
-	pub fn get_bucket_from_world_pos(&mut self, position: Vec2) -> &mut Vec<Id> {
-		let grid_space = self.to_grid_space(&position);
-		let id = Grid::pair(&grid_space);
-		self.get_bucket(id)
+	// Roughly how many buckets a ray of length distance along direction passes through. direction must be normalized
+	pub fn count_ray_buckets(&self, direction: &Vec2, distance: Geo) -> u64 {
+		let crossings = |axis: Geo| (axis.abs() as f64 * distance as f64 / self.bucket_size as f64).min(u64::MAX as f64 / 4.0) as u64;
+		crossings(direction.x) + crossings(direction.y) + 1
 	}
-	fn get_bucket(&mut self, bucket_id: GridPairId) -> &mut Vec<Id> {
-		if !self.buckets.contains_key(&bucket_id) {
-			self.buckets.insert(bucket_id, Vec::new());
+	// Visits every existing bucket the ray passes through, in order, until visit returns true or max_distance is reached
+	// visit also gets the distance along the ray where it leaves the bucket. direction must be normalized
+	pub fn traverse_ray<F>(&self, origin: &Vec2, direction: &Vec2, max_distance: Geo, mut visit: F) where F: FnMut(&Vec<Id>, Geo) -> bool {
+		// [Amanatides & Woo](http://www.cse.yorku.ca/~amana/research/grid.pdf) DDA
+		let mut cell = self.to_grid_space(origin);
+		let step_x: GridPosition = if direction.x > 0.0 { 1 } else { -1 };
+		let step_y: GridPosition = if direction.y > 0.0 { 1 } else { -1 };
+
+		// Distance along the ray to cross one bucket on each axis
+		let delta_x = if direction.x != 0.0 { self.bucket_size / direction.x.abs() } else { Geo::INFINITY };
+		let delta_y = if direction.y != 0.0 { self.bucket_size / direction.y.abs() } else { Geo::INFINITY };
+
+		// Distance along the ray to the next bucket boundary on each axis
+		let boundary_x = (cell.x + if step_x > 0 { 1 } else { 0 }) as Geo * self.bucket_size;
+		let boundary_y = (cell.y + if step_y > 0 { 1 } else { 0 }) as Geo * self.bucket_size;
+		let mut next_x = if direction.x != 0.0 { (boundary_x - origin.x) / direction.x } else { Geo::INFINITY };
+		let mut next_y = if direction.y != 0.0 { (boundary_y - origin.y) / direction.y } else { Geo::INFINITY };
+
+		loop {
+			let exit = next_x.min(next_y);
+			let bucket_id = Grid::pair(&cell);
+			for bucket in [self.buckets.get(&bucket_id), self.static_buckets.get(&bucket_id)].into_iter().flatten() {
+				if visit(bucket, exit.min(max_distance)) { return; }
+			}
+			if exit >= max_distance { return; }
+
+			if next_x < next_y {
+				cell.x += step_x;
+				next_x += delta_x;
+			}
+			else {
+				cell.y += step_y;
+				next_y += delta_y;
+			}
 		}
-		self.buckets.get_mut(&bucket_id).unwrap() // already know it contains key; unwrap is fine
+	}
+	fn get_grid_bounds(&self, bounds: &Bounds) -> GridBounds {
+		GridBounds::new(
+			self.to_grid_space(&bounds.min),
+			self.to_grid_space(&bounds.max)
+		)
 	}
 	pub fn insert_body(&mut self, body: &mut Body) {
-		let grid_bounds = GridBounds::new(
-			self.to_grid_space(&body.bounds.min),
-			self.to_grid_space(&body.bounds.max)
-		);
+		let grid_bounds = self.get_grid_bounds(&body.bounds);
 
 		if body.grid_spaces.len() > 0 {
 			self.remove_body(body);
@@ -100,30 +199,117 @@ impl Grid {
 		for y in grid_bounds.min.y..=grid_bounds.max.y {
 			for x in grid_bounds.min.x..=grid_bounds.max.x {
 				let bucket_id = Grid::pair(&GridVec::new(x, y));
-				let bucket = self.get_bucket(bucket_id);
-				bucket.push(body.id);
+				self.enter_bucket(bucket_id, body.id, body.is_static);
 				body.grid_spaces.push(bucket_id);
 			}
 		}
+		body.grid_bounds = Some(grid_bounds);
 	}
 	pub fn remove_body(&mut self, body: &mut Body) {
 		// crate::console_log!("-- Removing body {} --", body.id);
 		// crate::console_log!("grid cells: {:?}", body.grid_spaces.iter().map(|x| Grid::unpair(*x)));
 
-		let body_id = body.id;
 		for bucket_id in body.grid_spaces.iter() {
-			let bucket = self.get_bucket(*bucket_id);
-			let index = bucket.iter().position(|x| *x == body_id).unwrap();
-			bucket.remove(index);
+			self.leave_bucket(*bucket_id, body.id, body.is_static);
+		}
+		body.grid_spaces.clear();
+		body.grid_bounds = None;
+	}
+	// Moves body to the buckets its bounds cover now, only touching the buckets it entered or left
+	pub fn update_body(&mut self, body: &mut Body) {
+		let Some(old_bounds) = body.grid_bounds else {
+			self.insert_body(body);
+			return;
+		};
+		let new_bounds = self.get_grid_bounds(&body.bounds);
+		if old_bounds == new_bounds { return; } // still in the same buckets
 
-			if bucket.len() == 0 {
-				self.buckets.remove(bucket_id);
+		let body_id = body.id;
+		// Leave buckets that aren't covered anymore
+		for y in old_bounds.min.y..=old_bounds.max.y {
+			for x in old_bounds.min.x..=old_bounds.max.x {
+				if new_bounds.contains(x, y) { continue; }
+				self.leave_bucket(Grid::pair(&GridVec::new(x, y)), body_id, body.is_static);
 			}
 		}
+
+		// Enter newly covered buckets
 		body.grid_spaces.clear();
+		for y in new_bounds.min.y..=new_bounds.max.y {
+			for x in new_bounds.min.x..=new_bounds.max.x {
+				let bucket_id = Grid::pair(&GridVec::new(x, y));
+				if !old_bounds.contains(x, y) {
+					self.enter_bucket(bucket_id, body_id, body.is_static);
+				}
+				body.grid_spaces.push(bucket_id);
+			}
+		}
+		body.grid_bounds = Some(new_bounds);
 	}
-	pub fn update_body(&mut self, body: &mut Body) {
-		// todo: optimize this
+	// Adds body to the bucket in its layer, counting it as sharing a bucket with every body there that it can be paired with
+	fn enter_bucket(&mut self, bucket_id: GridPairId, body_id: Id, is_static: bool) {
+		for other_id in Grid::get_pairable(&self.buckets, &self.static_buckets, bucket_id, is_static) {
+			let (_, _, count) = self.shared.entry(CollisionPair::key(body_id, other_id))
+				.or_insert((body_id.min(other_id), body_id.max(other_id), 0));
+			*count += 1;
+		}
+		let layer = if is_static { &mut self.static_buckets } else { &mut self.buckets };
+		layer.entry(bucket_id).or_default().push(body_id);
+	}
+	// Removes body from the bucket in its layer, forgetting pairs that don't share any buckets anymore
+	fn leave_bucket(&mut self, bucket_id: GridPairId, body_id: Id, is_static: bool) {
+		let layer = if is_static { &mut self.static_buckets } else { &mut self.buckets };
+		let Some(bucket) = layer.get_mut(&bucket_id) else { return };
+		let Some(index) = bucket.iter().position(|x| *x == body_id) else { return };
+		bucket.swap_remove(index); // order in a bucket doesn't matter
+		if bucket.is_empty() {
+			layer.remove(&bucket_id);
+		}
+
+		for other_id in Grid::get_pairable(&self.buckets, &self.static_buckets, bucket_id, is_static) {
+			let key = CollisionPair::key(body_id, other_id);
+			let Some((_, _, count)) = self.shared.get_mut(&key) else { continue };
+			*count -= 1;
+			if *count == 0 {
+				self.shared.remove(&key);
+			}
+		}
+	}
+	// Bodies in the bucket a body can be paired with: dynamic ones pair with both layers, static ones only with dynamic bodies
+	// Takes the layers instead of self, so shared can be changed while going through them
+	fn get_pairable<'a>(buckets: &'a GridHashMap, static_buckets: &'a GridHashMap, bucket_id: GridPairId, is_static: bool) -> impl Iterator<Item = Id> + 'a {
+		let statics = if is_static { None } else { static_buckets.get(&bucket_id) };
+		buckets.get(&bucket_id).into_iter().chain(statics).flatten().copied()
+	}
+}
+
+impl Broadphase for Grid {
+	fn insert(&mut self, body: &mut Body) {
 		self.insert_body(body);
 	}
-}
\ No newline at end of file
+	fn remove(&mut self, body: &mut Body) {
+		self.remove_body(body);
+	}
+	fn update(&mut self, body: &mut Body) {
+		self.update_body(body);
+	}
+	fn pairs(&self, bodies: &BodyMap, pairs: &mut Vec<(Id, Id)>) {
+		// Each pair sharing a bucket is only in shared once, no matter how many buckets they share
+		for (body_a_id, body_b_id, _) in self.shared.values() {
+			let body_a = bodies.get(body_a_id).expect("failed to get body_a in Grid::pairs");
+			let body_b = bodies.get(body_b_id).expect("failed to get body_b in Grid::pairs");
+			if body_a.bounds.overlaps_with(&body_b.bounds) {
+				pairs.push((*body_a_id, *body_b_id));
+			}
+		}
+	}
+	fn query_aabb(&self, bounds: &Bounds) -> Vec<Id> {
+		Grid::query_aabb(self, bounds)
+	}
+	fn raycast(&self, origin: &Vec2, direction: &Vec2, max_distance: Geo, visit: &mut dyn FnMut(&[Id], Geo) -> bool) {
+		self.traverse_ray(origin, direction, max_distance, |bucket, exit| visit(bucket, exit));
+	}
+	fn as_grid(&self) -> Option<&Grid> {
+		Some(self)
+	}
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use nohash_hasher::BuildNoHashHasher;
+use serde::Serialize;
+
+use crate::{Body, BodyMap, Geo, Id, Time, Vec2};
+
+mod chain;
+pub use chain::{Chain, ChainOptions, ChainLink};
+
+pub type JointMap = HashMap<Id, Joint, BuildNoHashHasher<Id>>;
+
+#[derive(Serialize, Clone)]
+pub enum JointKind {
+	Revolute, // anchors are pinned together, bodies rotate freely around them
+	Prismatic { axis: Vec2, reference_angle: Geo }, // anchor b slides along axis (local to body a) through anchor a, without rotating
+	Rope { max_length: Geo }, // anchors can't get further than max_length apart, but can get closer
+	Pulley { ground_a: Vec2, ground_b: Vec2, ratio: Geo, length: Geo }, // ground_a to anchor_a + ratio * (ground_b to anchor_b) stays equal to length
+	Gear { joint_a: Id, joint_b: Id, ratio: Geo, constant: Geo }, // coordinate of joint_a + ratio * coordinate of joint_b stays constant
+}
+
+#[derive(Serialize, Clone)]
+pub struct Joint {
+	pub id: Id,
+	pub body_a: Id,
+	pub body_b: Id,
+	pub kind: JointKind,
+
+	// Anchors relative to each body's position, unrotated (same as Contact anchors)
+	pub anchor_a: Vec2,
+	pub anchor_b: Vec2,
+
+	// Whether the two bodies can still collide with each other
+	pub collide_connected: bool,
+
+	// Joint is removed when the reaction force / torque over a step goes past these
+	pub break_force: Option<Geo>,
+	pub break_torque: Option<Geo>,
+
+	// Impulses accumulated by the solver over the current step
+	pub impulse: Vec2,
+	pub axial_impulse: Geo,
+	pub angular_impulse: Geo,
+}
+
+static mut GLOBAL_JOINT_ID : Id = 0;
+
+impl Joint {
+	//
+	// constructors
+	//
+
+	fn new(body_a: Id, anchor_a: Vec2, body_b: Id, anchor_b: Vec2, kind: JointKind) -> Joint {
+		let id: Id;
+		unsafe {
+			id = GLOBAL_JOINT_ID;
+			GLOBAL_JOINT_ID += 1;
+		}
+
+		Joint {
+			id,
+			body_a,
+			body_b,
+			kind,
+
+			anchor_a,
+			anchor_b,
+
+			collide_connected: false,
+
+			break_force: None,
+			break_torque: None,
+
+			impulse: Vec2::zero(),
+			axial_impulse: 0.0,
+			angular_impulse: 0.0,
+		}
+	}
+	fn between(body_a: &Body, anchor_a: &Vec2, body_b: &Body, anchor_b: &Vec2, kind: JointKind) -> Joint {
+		Joint::new(body_a.id, Joint::to_local(body_a, anchor_a), body_b.id, Joint::to_local(body_b, anchor_b), kind)
+	}
+	// Pins body_a and body_b together at the world point anchor
+	pub fn revolute(body_a: &Body, body_b: &Body, anchor: Vec2) -> Joint {
+		Joint::between(body_a, &anchor, body_b, &anchor, JointKind::Revolute)
+	}
+	// Lets body_b slide along the world direction axis through the world point anchor, while keeping the bodies' relative angle
+	pub fn prismatic(body_a: &Body, body_b: &Body, anchor: Vec2, axis: Vec2) -> Joint {
+		let kind = JointKind::Prismatic {
+			axis: axis.normalize().rotate(-body_a.get_angle()),
+			reference_angle: body_b.get_angle() - body_a.get_angle(),
+		};
+		Joint::between(body_a, &anchor, body_b, &anchor, kind)
+	}
+	// Keeps the world points anchor_a and anchor_b at most max_length apart
+	pub fn rope(body_a: &Body, anchor_a: Vec2, body_b: &Body, anchor_b: Vec2, max_length: Geo) -> Joint {
+		Joint::between(body_a, &anchor_a, body_b, &anchor_b, JointKind::Rope { max_length })
+	}
+	// Hangs anchor_a and anchor_b from the fixed world points ground_a and ground_b with one rope. Pulling one side in by ratio lets the other out by 1
+	pub fn pulley(body_a: &Body, anchor_a: Vec2, body_b: &Body, anchor_b: Vec2, ground_a: Vec2, ground_b: Vec2, ratio: Geo) -> Joint {
+		let length = (anchor_a - ground_a).length() + ratio * (anchor_b - ground_b).length();
+		Joint::between(body_a, &anchor_a, body_b, &anchor_b, JointKind::Pulley { ground_a, ground_b, ratio, length })
+	}
+	// Couples two revolute or prismatic joints so that joint_a's coordinate + ratio * joint_b's coordinate stays constant
+	// Returns None if either joint is another kind or its bodies don't exist
+	pub fn gear(joint_a: &Joint, joint_b: &Joint, bodies: &BodyMap, ratio: Geo) -> Option<Joint> {
+		let constant = joint_a.get_coordinate(bodies)? + ratio * joint_b.get_coordinate(bodies)?;
+		let kind = JointKind::Gear { joint_a: joint_a.id, joint_b: joint_b.id, ratio, constant };
+		Some(Joint::new(joint_a.body_b, Vec2::zero(), joint_b.body_b, Vec2::zero(), kind))
+	}
+	pub fn collide_connected(mut self, c: bool) -> Self {
+		self.collide_connected = c;
+		self
+	}
+	pub fn break_force(mut self, f: Option<Geo>) -> Self {
+		self.break_force = f;
+		self
+	}
+	pub fn break_torque(mut self, t: Option<Geo>) -> Self {
+		self.break_torque = t;
+		self
+	}
+
+	//
+	// helpers
+	//
+
+	fn to_local(body: &Body, point: &Vec2) -> Vec2 {
+		(point - body.get_position()).rotate(-body.get_angle())
+	}
+	// Anchors rotated into world orientation, still relative to each body's position
+	pub fn get_world_radii(&self, body_a: &Body, body_b: &Body) -> (Vec2, Vec2) {
+		(self.anchor_a.rotate(body_a.get_angle()), self.anchor_b.rotate(body_b.get_angle()))
+	}
+	pub fn get_world_anchors(&self, body_a: &Body, body_b: &Body) -> (Vec2, Vec2) {
+		let (ra, rb) = self.get_world_radii(body_a, body_b);
+		(ra + body_a.get_position(), rb + body_b.get_position())
+	}
+	// Position along the joint's degree of freedom: relative angle for revolute joints, distance along the axis for prismatic joints
+	pub fn get_coordinate(&self, bodies: &BodyMap) -> Option<Geo> {
+		let body_a = bodies.get(&self.body_a)?;
+		let body_b = bodies.get(&self.body_b)?;
+		match self.kind {
+			JointKind::Revolute => Some(body_b.get_angle() - body_a.get_angle()),
+			JointKind::Prismatic { axis, .. } => {
+				let (anchor_a, anchor_b) = self.get_world_anchors(body_a, body_b);
+				Some(axis.rotate(body_a.get_angle()).dot(&(anchor_b - anchor_a)))
+			},
+			_ => None,
+		}
+	}
+	// Average force / torque the solver applied to keep the joint together over a step
+	pub fn get_reaction_force(&self, delta: Time) -> Geo {
+		match self.kind {
+			JointKind::Gear { .. } => 0.0,
+			_ => (self.impulse.length() + self.axial_impulse.abs()) / delta,
+		}
+	}
+	pub fn get_reaction_torque(&self, delta: Time) -> Geo {
+		match self.kind {
+			JointKind::Gear { .. } => self.axial_impulse.abs() / delta,
+			_ => self.angular_impulse.abs() / delta,
+		}
+	}
+	// Whether the last step's reaction force or torque was past the break thresholds
+	pub fn should_break(&self, delta: Time) -> bool {
+		self.break_force.is_some_and(|max| self.get_reaction_force(delta) > max)
+			|| self.break_torque.is_some_and(|max| self.get_reaction_torque(delta) > max)
+	}
+	pub fn reset_impulses(&mut self) {
+		self.impulse = Vec2::zero();
+		self.axial_impulse = 0.0;
+		self.angular_impulse = 0.0;
+	}
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsValue;
+
+use crate::{Body, BodyMap, BodyOptions, Geo, Id, Joint, Vec2, World};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChainLink {
+	Revolute, // rectangle links pinned end to end
+	Rope, // circle links that can bunch up but not stretch
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainOptions {
+	pub links: u16,
+	pub thickness: Geo,
+	pub link: ChainLink,
+	pub body: BodyOptions,
+	pub break_force: Option<Geo>, // applied to every joint in the chain
+}
+
+impl Default for ChainOptions {
+	fn default() -> Self {
+		Self {
+			links: 10,
+			thickness: 8.0,
+			link: ChainLink::Revolute,
+			body: BodyOptions::default(),
+			break_force: None,
+		}
+	}
+}
+
+impl From<JsValue> for ChainOptions {
+	fn from(value: JsValue) -> ChainOptions {
+		if value.is_null_or_undefined() {
+			return ChainOptions::default();
+		}
+
+		serde_wasm_bindgen::from_value(value)
+			.unwrap_or_else(|error| {
+				web_sys::console::warn_1(&format!("Failed to parse ChainOptions: {}. Using defaults.", error).into());
+				ChainOptions::default()
+			})
+	}
+}
+
+// Ids of everything created by Chain::create, in order from anchor_a to anchor_b
+#[derive(Serialize)]
+pub struct Chain {
+	pub bodies: Vec<Id>,
+	pub joints: Vec<Id>,
+}
+
+impl Chain {
+	// Creates a chain of bodies hanging between anchor_a on body_a and anchor_b on body_b, and adds everything to the world
+	pub fn create(world: &mut World, bodies: &mut BodyMap, body_a: Id, anchor_a: Vec2, body_b: Id, anchor_b: Vec2, options: ChainOptions) -> Chain {
+		let mut chain = Chain {
+			bodies: Vec::new(),
+			joints: Vec::new(),
+		};
+		if !bodies.contains_key(&body_a) || !bodies.contains_key(&body_b) { return chain } // Body doesn't exist
+
+		let links = options.links.max(1);
+		let span = anchor_b - anchor_a;
+		let segment = span / links as Geo;
+		let segment_length = segment.length();
+		let angle = segment.y.atan2(segment.x);
+
+		// Create links along the straight line from anchor_a to anchor_b
+		for i in 0..links {
+			let start = anchor_a + segment * i as Geo;
+			let center = start + segment * 0.5;
+			let mut body = match options.link {
+				ChainLink::Revolute => Body::rectangle(segment_length.max(options.thickness), options.thickness, center, options.body.clone()),
+				ChainLink::Rope => Body::circle(options.thickness / 2.0, center, options.body.clone()),
+			};
+			body.set_angle(angle);
+			world.add_body(&mut body);
+			chain.bodies.push(body.id);
+			bodies.insert(body.id, body);
+		}
+
+		// Link everything together, including the two anchor bodies at either end
+		let mut link_ids = Vec::with_capacity(chain.bodies.len() + 2);
+		link_ids.push(body_a);
+		link_ids.extend_from_slice(&chain.bodies);
+		link_ids.push(body_b);
+		for i in 0..link_ids.len() - 1 {
+			let prev = bodies.get(&link_ids[i]).unwrap(); // unwrap ok, every id was checked or created above
+			let next = bodies.get(&link_ids[i + 1]).unwrap();
+			let point = anchor_a + segment * i as Geo;
+			let joint = match options.link {
+				ChainLink::Revolute => Joint::revolute(prev, next, point),
+				ChainLink::Rope => {
+					// Rope links connect centers, except at the ends where they connect to the anchors
+					let prev_point = if i == 0 { anchor_a } else { point - segment * 0.5 };
+					let next_point = if i == link_ids.len() - 2 { anchor_b } else { point + segment * 0.5 };
+					let max_length = (next_point - prev_point).length();
+					Joint::rope(prev, prev_point, next, next_point, max_length)
+				},
+			}.break_force(options.break_force);
+			chain.joints.push(joint.id);
+			world.add_joint(joint);
+		}
+
+		chain
+	}
+}
@@ -18,19 +18,40 @@ pub use color::Color;
 mod collision_pair;
 pub use collision_pair::CollisionPair;
 
+mod shape;
+pub use shape::{Shape, ShapeDef};
+
 mod body;
-pub use body::{Body, BodyOptions};
+pub use body::{Body, BodyOptions, Edge, EdgeChain, EdgeChainOptions};
+
+mod event;
+pub use event::{Event, CollisionEvent};
+
+mod joint;
+pub use joint::{Joint, JointKind, JointMap, Chain, ChainLink, ChainOptions};
 
 mod world;
 pub use world::World;
 
+mod tilemap;
+pub use tilemap::{Tilemap, TilemapOptions, TileRect};
+
 mod physics;
 
 mod engine;
-pub use engine::{Engine, BodyMap};
+pub use engine::{Engine, EngineOptions, BodyMap};
 
 mod bounds;
 pub use bounds::Bounds;
 
 mod grid;
 pub use grid::Grid;
+
+mod aabb_tree;
+pub use aabb_tree::AabbTree;
+
+mod broadphase;
+pub use broadphase::{Broadphase, BroadphaseKind, BruteForce, SweepAndPrune};
+
+mod query;
+pub use query::{Distance, QueryFilter, RaycastHit, ShapeCastHit};
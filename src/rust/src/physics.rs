@@ -1,6 +1,9 @@
 use crate::{BodyMap, Time, World};
 mod collisions;
+pub use collisions::run_pre_solve;
 mod solver;
+mod joint_solver;
+mod ccd;
 
 // todo: add physics options struct
 
@@ -10,27 +13,40 @@ physics update steps:
 		Get all pairs of bodies
 		Check if they collide
 			Create manifold (collision_pair) if they do
+			Or a speculative one if they might collide this step
 	Apply forces
 		Gravity
+		User applied forces / torques
 	Solve velocity constraints
+		Contacts
+		Joints
+	Break joints
+	Queue collision events
 	Solve position constraints
+	Apply damping
 	Update positions / angles
+		Bullets are swept to their first time of impact instead of moving straight through
 */
 pub fn update(world: &mut World, bodies: &mut BodyMap, delta: Time) {
-	collisions::find(world, bodies);
+	collisions::find(world, bodies, delta);
 	apply_forces(world, bodies, delta); // applies gravity (and other forces)
 	
 	// Solve velocities
 	let velocity_iterations = 8;
+	joint_solver::prepare(world);
 	for _ in 0..velocity_iterations {
-		solver::solve_velocity(world, bodies, delta / velocity_iterations as f32);
+		solver::solve_velocity(world, bodies, delta / velocity_iterations as f32, delta);
+		joint_solver::solve_velocity(world, bodies, delta); // full step, see joint_solver::solve_velocity
 	}
+	joint_solver::break_joints(world, delta);
+	collisions::queue_events(world);
 
 	// todo: solve position constraints
 	apply_velocities(world, bodies, delta);
+	ccd::move_bullets(world, bodies, delta);
 	
-	// update broadphase grid with new positions
-	world.update_grid(bodies);
+	// update broadphase with new positions
+	world.update_broadphase(bodies);
 
 	// Increment world time
 	world.frame += 1;
@@ -39,11 +55,19 @@ pub fn update(world: &mut World, bodies: &mut BodyMap, delta: Time) {
 
 // Applying forces/velocities
 fn apply_forces(world: &mut World, bodies: &mut BodyMap, delta: Time) {
-	let gravity = &world.gravity * &delta;
 	for body_id in world.bodies.iter() {
 		let body = bodies.get_mut(body_id).unwrap();
-		if body.is_static { continue; } // Don't apply forces to static bodies
+		if body.is_static { // Don't apply forces to static bodies
+			body.clear_forces();
+			continue;
+		}
+		let gravity = body.get_gravity(&world.gravity) * delta;
 		body.apply_velocity(&gravity);
+
+		// User applied forces
+		body.velocity += body.force * body.get_axis_inverse_mass() * delta;
+		body.angular_velocity += body.torque * body.get_rotational_inverse_inertia() * delta;
+		body.clear_forces();
 	}
 }
 fn apply_velocities(world: &mut World, bodies: &mut BodyMap, delta: Time) {
@@ -51,6 +75,17 @@ fn apply_velocities(world: &mut World, bodies: &mut BodyMap, delta: Time) {
 	for body_id in world.bodies.iter() {
 		let body = bodies.get_mut(body_id).unwrap();
 		if body.is_static { continue; } // Don't move static bodies
+
+		// Locked axes / rotation never move, whatever the solver or user did to their velocity
+		if body.lock_x { body.velocity.x = 0.0; }
+		if body.lock_y { body.velocity.y = 0.0; }
+		if body.fixed_rotation { body.angular_velocity = 0.0; }
+
+		// Damping; exponential decay so it's the same regardless of delta
+		body.velocity *= (-(body.linear_damping + world.air_drag) * delta).exp();
+		body.angular_velocity *= (-(body.angular_damping + world.air_drag) * delta).exp();
+
+		if body.bullet { continue; } // Moved by ccd::move_bullets
 		body.translate_position(body.get_velocity() * delta); // todo: average cur velocity with last velocity for trapezoidal approx
 		body.translate_angle(body.angular_velocity * delta);
 	}
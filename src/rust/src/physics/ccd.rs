@@ -0,0 +1,140 @@
+use std::collections::HashSet;
+
+use crate::{Body, BodyMap, CollisionPair, Geo, Id, PairId, Time, Vec2, World, query::{Impact, get_candidates, time_of_impact}, shape::get_swept_bounds};
+
+const MAX_SUBSTEPS: usize = 4;
+const SLOP: Geo = 0.01; // how far before the time of impact bullets stop, so they don't start the next substep touching
+
+// Moves bullets by their velocity, stopping at the first body in the way, bouncing off it, and continuing with the rest of the step
+// Bodies a bullet already overlaps are left for the regular solver
+pub fn move_bullets(world: &World, bodies: &mut BodyMap, delta: Time) {
+	let bullets: Vec<Id> = world.bodies.iter()
+		.copied()
+		.filter(|body_id| {
+			let body = bodies.get(body_id).unwrap();
+			body.bullet && !body.is_static
+		})
+		.collect();
+	if bullets.is_empty() { return; }
+	let jointed_pairs = world.get_jointed_pairs();
+
+	for body_id in bullets {
+		let mut remaining: Geo = 1.0 + bodies.get(&body_id).unwrap().ccd_carry; // fraction of delta left to move
+		for _ in 0..MAX_SUBSTEPS {
+			let body = bodies.get(&body_id).unwrap();
+			let translation = body.velocity * delta * remaining;
+			let length = translation.length();
+			if length == 0.0 {
+				remaining = 0.0;
+				break;
+			}
+
+			let Some((target_id, impact)) = find_impact(world, bodies, body, &translation, &jointed_pairs) else {
+				bodies.get_mut(&body_id).unwrap().translate_position(translation);
+				remaining = 0.0;
+				break;
+			};
+
+			// Move up to the impact, then bounce off and spend the rest of the step moving away
+			let fraction = ((impact.fraction * length - SLOP) / length).max(0.0);
+			bodies.get_mut(&body_id).unwrap().translate_position(translation * fraction);
+			resolve_impact(bodies, body_id, target_id, &impact.normal);
+			remaining *= 1.0 - fraction; // what it actually moved, which stopped SLOP short of the impact
+		}
+
+		// Out of substeps, e.g. bouncing around in a tight corner. The rest is moved next step instead of being lost
+		// At most a step's worth, so a bullet that's stuck doesn't build up more and more
+		let body = bodies.get_mut(&body_id).unwrap();
+		body.ccd_carry = remaining.min(1.0);
+		body.translate_angle(body.angular_velocity * delta);
+	}
+}
+
+// Earliest body in the way of body moving by translation, and where it's hit
+fn find_impact(world: &World, bodies: &BodyMap, body: &Body, translation: &Vec2, jointed_pairs: &HashSet<PairId>) -> Option<(Id, Impact)> {
+	let swept_bounds = get_swept_bounds(body.get_vertices(), translation);
+
+	let mut best: Option<(Id, Impact)> = None;
+	for target_id in get_candidates(world, &swept_bounds) {
+		if target_id == body.id || jointed_pairs.contains(&CollisionPair::key(body.id, target_id)) { continue; }
+		let target = bodies.get(&target_id).expect("failed to get body in ccd::find_impact");
+		if !(target.is_static || world.ccd_dynamic) || !target.bounds.overlaps_with(&swept_bounds) { continue; }
+
+		let Some(impact) = time_of_impact(body.get_vertices(), translation, target.get_vertices()) else { continue };
+		if impact.fraction <= 0.0 { continue; } // already overlapping / touching, the solver handles it
+		if best.as_ref().is_none_or(|(_, best)| impact.fraction < best.fraction) {
+			best = Some((target_id, impact));
+		}
+	}
+	best
+}
+
+// Removes the bullet's velocity into target along normal (from target toward the bullet), with restitution
+// Dynamic targets get the opposite impulse
+fn resolve_impact(bodies: &mut BodyMap, bullet_id: Id, target_id: Id, normal: &Vec2) {
+	let bullet = bodies.get(&bullet_id).unwrap();
+	let target = bodies.get(&target_id).unwrap();
+	let restitution = bullet.restitution.max(target.restitution);
+
+	// Static bodies act like they have infinite mass, same as in the joint solver
+	let (target_velocity, target_inverse_mass) = if target.is_static { (Vec2::zero(), Vec2::zero()) } else { (*target.get_velocity(), target.get_axis_inverse_mass()) };
+	let closing_speed = (bullet.get_velocity() - target_velocity).dot(normal);
+	if closing_speed >= 0.0 { return; } // already moving apart
+
+	let k = (bullet.get_axis_inverse_mass() + target_inverse_mass).dot(&(normal * normal));
+	if k == 0.0 { return; }
+	let impulse = normal * (-(1.0 + restitution) * closing_speed / k);
+
+	bodies.get_mut(&bullet_id).unwrap().apply_impulse(&impulse);
+	bodies.get_mut(&target_id).unwrap().apply_impulse(&-impulse);
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{Body, BodyMap, BodyOptions, EngineOptions, Geo, Id, Vec2, World, physics};
+
+	// A bouncy bullet at the origin moving right at speed, and static bouncy walls given as (left face, width)
+	fn setup(speed: Geo, walls: &[(Geo, Geo)]) -> (World, BodyMap, Id) {
+		let mut world = World::from_options(&EngineOptions::new());
+		world.gravity = Vec2::zero();
+		let mut bodies = BodyMap::default();
+		for (x, width) in walls {
+			let mut wall = Body::rectangle(*width, 200.0, Vec2::new(x + width / 2.0, 0.0), BodyOptions::new().is_static(true).restitution(1.0));
+			world.add_body(&mut wall);
+			bodies.insert(wall.id, wall);
+		}
+		let mut bullet = Body::rectangle(10.0, 10.0, Vec2::zero(), BodyOptions::new().bullet(true).restitution(1.0));
+		bullet.set_velocity(Vec2::new(speed, 0.0));
+		let id = bullet.id;
+		world.add_body(&mut bullet);
+		bodies.insert(id, bullet);
+		(world, bodies, id)
+	}
+
+	#[test]
+	fn bounce_covers_the_whole_step() {
+		// 30 to the wall, stopping SLOP short, then the rest of the 60 back
+		let (mut world, mut bodies, id) = setup(3600.0, &[(35.0, 20.0)]);
+		physics::update(&mut world, &mut bodies, 1.0 / 60.0);
+		let body = &bodies[&id];
+		assert!(body.get_velocity().x < 0.0, "bullet should bounce off the wall");
+		assert!((body.get_position().x + 2.0 * super::SLOP).abs() < 0.005, "bullet should move 60 in total, ended at {}", body.get_position().x);
+		assert_eq!(body.ccd_carry, 0.0);
+	}
+
+	#[test]
+	fn leftover_motion_carries_over() {
+		// Bouncing between walls 10 apart more often than there are substeps
+		let (mut world, mut bodies, id) = setup(6000.0, &[(-15.0, 10.0), (15.0, 10.0)]);
+		physics::update(&mut world, &mut bodies, 1.0 / 60.0);
+		let carry = bodies[&id].ccd_carry;
+		assert!(carry > 0.0 && carry <= 1.0, "bullet should have motion left over, got {}", carry);
+
+		for _ in 0..10 {
+			physics::update(&mut world, &mut bodies, 1.0 / 60.0);
+			let body = &bodies[&id];
+			assert!(body.get_position().x.abs() < 10.0, "bullet escaped the walls to {}", body.get_position().x);
+			assert!(body.ccd_carry <= 1.0);
+		}
+	}
+}
@@ -1,17 +1,159 @@
-use crate::{Body, BodyMap, Geo, Vec2, World, collision_pair::{CollisionPair, Contact}};
+use serde::Deserialize;
+use wasm_bindgen::JsValue;
 
-pub fn find(world: &mut World, bodies: &mut BodyMap) {
-	let pairs = world.find_pairs(bodies);
+use std::collections::HashSet;
 
-	for pair in pairs {
+use crate::{Body, BodyMap, CollisionEvent, Event, Geo, Id, PairId, Time, Vec2, World, collision_pair::{CollisionPair, Contact, DisabledPair}, query, shape::get_swept_bounds};
+
+const EDGE_AXIS_TOLERANCE: Geo = 0.1; // how much less a polygon face has to overlap an edge than the edge's normal to be used instead
+
+// What a pre-solve callback can change about a pair for the current step
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct PreSolveResult {
+	enabled: Option<bool>,
+	friction: Option<Geo>,
+	restitution: Option<Geo>,
+}
+
+pub fn find(world: &mut World, bodies: &mut BodyMap, delta: Time) {
+	let mut pairs = std::mem::take(&mut world.pair_buffer); // given back at the end, so next step reuses the allocation
+	world.find_pairs(bodies, &mut pairs);
+	if world.speculative_contacts {
+		find_speculative_pairs(world, bodies, &mut pairs, delta);
+	}
+	let jointed_pairs = world.get_jointed_pairs();
+
+	for pair in pairs.iter() {
+		let key = CollisionPair::key(pair.0, pair.1);
+		if jointed_pairs.contains(&key) { continue; } // connected bodies don't collide
 		let body_a = bodies.get(&pair.0).unwrap();
 		let body_b = bodies.get(&pair.1).unwrap();
-		if !collides(body_a, body_b) { continue; }
 
-		// Create manifold (collision_pair) if they collide and add to world pairs (if it doesn't already exist)
-		// let pair_id = CollisionPair::pair_id(body_a.id, body_b.id);
-		world.collision_pairs.replace(create_manifold(world, body_a, body_b));
+		// Create manifold (collision_pair) if they collide and add to world pairs, replacing last frame's
+		let mut manifold = if collides(body_a, body_b) {
+			if body_a.is_edge() || body_b.is_edge() {
+				let (edge, body) = if body_a.is_edge() { (body_a, body_b) } else { (body_b, body_a) };
+				let Some(manifold) = create_edge_manifold(world, edge, body) else { continue }; // touching somewhere a neighbouring edge handles
+				manifold
+			}
+			else {
+				create_manifold(world, body_a, body_b)
+			}
+		}
+		else if world.speculative_contacts && get_swept_bounds(body_a.get_vertices(), &(body_a.velocity * delta)).overlaps_with(&get_swept_bounds(body_b.get_vertices(), &(body_b.velocity * delta))) {
+			create_speculative_manifold(world, body_a, body_b)
+		}
+		else {
+			continue;
+		};
+		let was_touching = world.collision_pairs.get(&key).is_some_and(|old| old.frame + 1 >= world.frame && !old.speculative);
+		if let Some(old) = world.collision_pairs.get(&key) {
+			if was_touching {
+				if manifold.speculative { // stopped touching, but is still close
+					world.queue_event(Event::CollisionEnd(CollisionEvent::from_pair(old)));
+				}
+				else {
+					manifold.start_frame = old.start_frame;
+				}
+			}
+		}
+		if manifold.speculative { // not touching yet, but pre-solve still decides whether they can pass through each other
+			if world.pre_solve.as_ref().is_none_or(|pre_solve| pre_solve(&mut manifold)) {
+				world.collision_pairs.insert(key, manifold);
+			}
+			continue;
+		}
+
+		// Pairs that were disabled last frame keep their start frame too, so is_new stays false while they keep touching
+		let disabled = world.disabled_pairs.remove(&key).filter(|disabled| disabled.frame + 1 >= world.frame);
+		if let Some(disabled) = &disabled { manifold.start_frame = disabled.start_frame; }
+		let enabled = match &world.pre_solve {
+			Some(pre_solve) => pre_solve(&mut manifold),
+			None => true,
+		};
+		if !enabled { // taken out of collision_pairs, but End is only sent once it stops touching, see below
+			let last_enabled = if was_touching { world.collision_pairs.remove(&key) } else { disabled.and_then(|disabled| disabled.last_enabled) };
+			world.disabled_pairs.insert(key, DisabledPair { start_frame: manifold.start_frame, frame: world.frame, last_enabled });
+			continue;
+		}
+		if disabled.is_some_and(|disabled| disabled.last_enabled.is_none()) {
+			manifold.start_frame = world.frame; // Begin was never sent while it was disabled, so it begins now
+		}
+		world.collision_pairs.insert(key, manifold);
 	}
+	world.pair_buffer = pairs;
+
+	// Disabled pairs that weren't disabled again this frame stopped touching (enabled ones were moved back into collision_pairs)
+	let frame = world.frame;
+	let record_events = world.record_events;
+	let events = &mut world.events;
+	world.disabled_pairs.retain(|_, disabled| {
+		if disabled.frame == frame { return true; }
+		if let (true, Some(pair)) = (record_events, &disabled.last_enabled) {
+			events.push(Event::CollisionEnd(CollisionEvent::from_pair(pair)));
+		}
+		false
+	});
+
+	// Pairs that weren't found this frame stopped touching
+	world.collision_pairs.retain(|_, pair| {
+		if pair.is_valid(frame) { return true; }
+		if record_events && !pair.speculative {
+			events.push(Event::CollisionEnd(CollisionEvent::from_pair(pair)));
+		}
+		false
+	});
+}
+
+// Queues begin / persist events for every touching pair. Called after solving so impulses are known
+pub fn queue_events(world: &mut World) {
+	if !world.record_events { return; }
+	for pair in world.collision_pairs.values() {
+		if pair.speculative { continue; } // not touching
+		let event = CollisionEvent::from_pair(pair);
+		world.events.push(if pair.is_new() { Event::CollisionBegin(event) } else { Event::CollisionPersist(event) });
+	}
+}
+
+// Adds pairs whose bounds don't overlap yet, but will somewhere along their movement this step
+fn find_speculative_pairs(world: &World, bodies: &BodyMap, pairs: &mut Vec<(Id, Id)>, delta: Time) {
+	let mut found: HashSet<PairId> = pairs.iter().map(|pair| CollisionPair::key(pair.0, pair.1)).collect();
+	for body_id in world.bodies.iter() {
+		let body = bodies.get(body_id).unwrap();
+		if body.is_static || body.velocity.length() == 0.0 { continue; }
+		let swept_bounds = get_swept_bounds(body.get_vertices(), &(body.velocity * delta));
+
+		for other_id in query::get_candidates(world, &swept_bounds) {
+			if other_id == *body_id || !found.insert(CollisionPair::key(*body_id, other_id)) { continue; }
+			pairs.push((*body_id, other_id)); // swept bounds are checked again in find
+		}
+	}
+}
+
+// Calls the JS pre-solve callback with (body_a, body_b, is_new, touching) and applies its overrides to pair. Used as World::pre_solve by Engine::world_set_pre_solve
+// The callback can return false to disable the pair this step, or { enabled, friction, restitution } with any of the fields set
+// Speculative pairs are passed with touching false and is_new false. is_new stays false while a pair keeps touching, even on steps it was disabled, see World::disabled_pairs
+// The engine is mutably borrowed while this runs, so the callback can't call back into it: wasm-bindgen throws, which is only logged as a warning here
+pub fn run_pre_solve(callback: &js_sys::Function, pair: &mut CollisionPair) -> bool {
+	let result = match callback.call4(&JsValue::NULL, &pair.body_a.into(), &pair.body_b.into(), &(pair.is_new() && !pair.speculative).into(), &(!pair.speculative).into()) {
+		Ok(result) => result,
+		Err(error) => {
+			web_sys::console::warn_2(&"Pre-solve callback threw, ignoring it:".into(), &error);
+			return true;
+		},
+	};
+	if result.is_null_or_undefined() { return true; }
+	if let Some(enabled) = result.as_bool() { return enabled; }
+
+	let result: PreSolveResult = serde_wasm_bindgen::from_value(result)
+		.unwrap_or_else(|error| {
+			web_sys::console::warn_1(&format!("Failed to parse pre-solve result: {}. Ignoring it.", error).into());
+			PreSolveResult::default()
+		});
+	if let Some(friction) = result.friction { pair.friction = friction; }
+	if let Some(restitution) = result.restitution { pair.restitution = 1.0 + restitution; } // stored as 1 + restitution, see create_manifold
+	result.enabled.unwrap_or(true)
 }
 
 fn collides(body_a: &Body, body_b: &Body) -> bool {
@@ -116,11 +258,17 @@ fn create_manifold(world: &World, body_a: &Body, body_b: &Body) -> CollisionPair
 	let reference_body = if body_a.id == reference_body_id { body_a } else { body_b };
 	let incident_body = if body_a.id == incident_body_id { body_a } else { body_b };
 	let mass_coef = 1.0 / contacts.len() as Geo;
+	let mut approach_speed: Geo = 0.0;
 	for contact in contacts.iter_mut() {
 		let vertex = &contact.vertex.clone();
 		contact.anchor_a = (vertex - reference_body.get_position()).rotate(-reference_body.get_angle());
 		contact.anchor_b = (vertex - incident_body.get_position()).rotate(-incident_body.get_angle());
 		contact.mass_coefficient = mass_coef;
+
+		// Relative velocity along the normal at this contact, same as solver::solve_velocity
+		let vra = reference_body.get_velocity() + (vertex - reference_body.get_position()).cross_float(reference_body.angular_velocity);
+		let vrb = incident_body.get_velocity() + (vertex - incident_body.get_position()).cross_float(incident_body.angular_velocity);
+		approach_speed = approach_speed.max((vrb - vra).dot(&normal));
 	}
 
 
@@ -138,5 +286,277 @@ fn create_manifold(world: &World, body_a: &Body, body_b: &Body) -> CollisionPair
 
 		friction: (body_a.friction.powi(2) + body_b.friction.powi(2)).sqrt(),
 		restitution: 1.0 + body_a.restitution.max(body_b.restitution),
+
+		start_frame: world.frame,
+		approach_speed,
+		impulse: 0.0,
+
+		speculative: false,
+	}
+}
+
+// Manifold between an edge and a polygon, with body_a the polygon and the normal pointing from the edge toward it
+// Normally uses the edge's normal. The polygon's own face normals are only used around the edge's ends, and only if there's no
+// ghost vertex there saying the corner is flat or concave, so bodies slide across the joins in a chain instead of catching on them
+// Returns None if none of the polygon is over this edge's part of the chain
+fn create_edge_manifold(world: &World, edge: &Body, body: &Body) -> Option<CollisionPair> {
+	let start = edge.get_vertices()[0];
+	let end = edge.get_vertices()[1];
+	let vertices = body.get_vertices();
+
+	// Edges are two sided, so collide on whichever side the body's center is
+	let mut edge_normal = (end - start).normal().normalize();
+	if edge_normal.dot(&(body.position - start)) < 0.0 { edge_normal = -edge_normal; }
+	let edge_separation = vertices.iter().map(|vertex| edge_normal.dot(&(vertex - start))).fold(Geo::MAX, Geo::min);
+
+	// Face of the polygon the edge is least inside of
+	let mut face = (0, Geo::MIN); // index, separation
+	for i in 0..vertices.len() {
+		let face_normal = (vertices[(i + 1) % vertices.len()] - vertices[i]).normal().normalize();
+		let separation = face_normal.dot(&(start - vertices[i])).min(face_normal.dot(&(end - vertices[i])));
+		if separation > face.1 {
+			face = (i, separation);
+		}
+	}
+
+	let face_start = vertices[face.0];
+	let face_end = vertices[(face.0 + 1) % vertices.len()];
+	let face_normal = (face_end - face_start).normal().normalize();
+	let use_face = face.1 > edge_separation + EDGE_AXIS_TOLERANCE && {
+		// Corner the polygon is hitting, and the edge continuing past it
+		let (corner, ghost) = if face_normal.dot(&(start - face_start)) < face_normal.dot(&(end - face_start)) { (start, edge.get_ghost_vertices().0) } else { (end, edge.get_ghost_vertices().1) };
+		match ghost {
+			None => true, // end of the chain, the corner is real
+			Some(ghost) => {
+				// Only use the face if the corner sticks out, and its normal is between this edge's and the next one's
+				let mut ghost_normal = (ghost - corner).normal().normalize();
+				if ghost_normal.dot(&edge_normal) < 0.0 { ghost_normal = -ghost_normal; }
+				let convex = (ghost - corner).dot(&edge_normal) < 0.0;
+				let cone = edge_normal.dot(&ghost_normal);
+				convex && (-face_normal).dot(&edge_normal) >= cone && (-face_normal).dot(&ghost_normal) >= cone
+			},
+		}
+	};
+
+	// Clip the incident face against the reference face, keeping the points behind it
+	let (normal, points) = if use_face {
+		(-face_normal, clip_segment(&face_start, &face_end, &face_normal, &start, &end))
+	}
+	else {
+		let incident = (0..vertices.len())
+			.min_by(|a, b| {
+				let normal_of = |i: usize| (vertices[(i + 1) % vertices.len()] - vertices[i]).normal().normalize().dot(&edge_normal);
+				normal_of(*a).total_cmp(&normal_of(*b))
+			})
+			.unwrap(); // unwrap ok, polygons aren't empty
+		(edge_normal, clip_segment(&start, &end, &edge_normal, &vertices[incident], &vertices[(incident + 1) % vertices.len()]))
+	};
+	if points.is_empty() { return None; }
+
+	let mass_coef = 1.0 / points.len() as Geo;
+	let mut depth: Geo = 0.0;
+	let mut approach_speed: Geo = 0.0;
+	let mut normal_point = Vec2::zero();
+	let mut contacts = Vec::new();
+	for (vertex, separation) in points.iter() {
+		depth = depth.max(-separation);
+		normal_point += vertex * mass_coef;
+
+		// Relative velocity along the normal at this contact, same as create_manifold. Edges are static
+		let vra = body.get_velocity() + (vertex - body.get_position()).cross_float(body.angular_velocity);
+		approach_speed = approach_speed.max(-vra.dot(&normal));
+
+		contacts.push(Contact {
+			vertex: *vertex,
+			reference: if use_face { body.id } else { edge.id },
+			incident: if use_face { edge.id } else { body.id },
+			anchor_a: (vertex - body.get_position()).rotate(-body.get_angle()),
+			anchor_b: (vertex - edge.get_position()).rotate(-edge.get_angle()),
+			mass_coefficient: mass_coef,
+		});
+	}
+
+	Some(CollisionPair {
+		body_a: body.id,
+		body_b: edge.id,
+		frame: world.frame,
+
+		contacts,
+
+		depth,
+		tangent: normal.normal(),
+		normal,
+		normal_point,
+
+		friction: (body.friction.powi(2) + edge.friction.powi(2)).sqrt(),
+		restitution: 1.0 + body.restitution.max(edge.restitution),
+
+		start_frame: world.frame,
+		approach_speed,
+		impulse: 0.0,
+
+		speculative: false,
+	})
+}
+
+// Cuts the incident segment down to the part alongside the reference face, and returns its ends that are behind or just in front of
+// the face, with how far in front they are (negative when behind). face_normal faces away from the reference face, toward the incident one
+fn clip_segment(face_start: &Vec2, face_end: &Vec2, face_normal: &Vec2, incident_start: &Vec2, incident_end: &Vec2) -> Vec<(Vec2, Geo)> {
+	let tangent = (face_end - face_start).normalize();
+	let mut points = [*incident_start, *incident_end];
+	for (limit, direction) in [(face_start.dot(&tangent), 1.0), (face_end.dot(&tangent), -1.0)] {
+		let d0 = (points[0].dot(&tangent) - limit) * direction; // negative past the limit
+		let d1 = (points[1].dot(&tangent) - limit) * direction;
+		if d0 < 0.0 && d1 < 0.0 { return Vec::new(); } // all of it is past the end of the face
+
+		let clipped = points[0] + (points[1] - points[0]) * (d0 / (d0 - d1));
+		if d0 < 0.0 { points[0] = clipped; }
+		else if d1 < 0.0 { points[1] = clipped; }
+	}
+
+	points.iter()
+		.map(|point| (*point, face_normal.dot(&(point - face_start))))
+		.filter(|(_, separation)| *separation < 0.01) // 0.01 for precision error, same as Body::contains_point
+		.collect()
+}
+
+// Manifold for two bodies that aren't touching yet: one contact between their closest points, with depth = -gap
+// The solver only uses it to remove the approach speed that would close the gap this step
+fn create_speculative_manifold(world: &World, body_a: &Body, body_b: &Body) -> CollisionPair {
+	let distance = query::body_distance(body_a, body_b);
+	let normal = -distance.normal; // from b toward a, same as create_manifold
+	let vertex = distance.point_b;
+
+	CollisionPair {
+		body_a: body_a.id,
+		body_b: body_b.id,
+		frame: world.frame,
+
+		// Both anchors are at the same point so the solver's separation is just depth
+		contacts: vec![Contact {
+			vertex,
+			reference: body_a.id,
+			incident: body_b.id,
+			anchor_a: (vertex - body_a.get_position()).rotate(-body_a.get_angle()),
+			anchor_b: (vertex - body_b.get_position()).rotate(-body_b.get_angle()),
+			mass_coefficient: 1.0,
+		}],
+
+		depth: -distance.distance.max(0.0),
+		tangent: normal.normal(),
+		normal,
+		normal_point: vertex,
+
+		friction: (body_a.friction.powi(2) + body_b.friction.powi(2)).sqrt(),
+		restitution: 1.0 + body_a.restitution.max(body_b.restitution),
+
+		start_frame: world.frame,
+		approach_speed: 0.0,
+		impulse: 0.0,
+
+		speculative: true,
+	}
+}
+#[cfg(test)]
+mod tests {
+	use std::{cell::Cell, rc::Rc};
+
+	use crate::{Body, BodyMap, BodyOptions, EngineOptions, Event, Id, Vec2, World, physics};
+
+	// Steps the world once and returns the collision events it queued, as B(egin), P(ersist) and E(nd)
+	fn step(world: &mut World, bodies: &mut BodyMap) -> String {
+		physics::update(world, bodies, 1.0 / 60.0);
+		world.events.drain(..).filter_map(|event| match event {
+			Event::CollisionBegin(_) => Some('B'),
+			Event::CollisionPersist(_) => Some('P'),
+			Event::CollisionEnd(_) => Some('E'),
+			_ => None,
+		}).collect()
+	}
+
+	// A box resting on static ground, with a pre-solve callback that enables the pair whenever enabled is set
+	fn setup() -> (World, BodyMap, Id, Rc<Cell<bool>>) {
+		let mut world = World::from_options(&EngineOptions::new());
+		world.record_events = true;
+		let mut bodies = BodyMap::default();
+		let mut ground = Body::rectangle(200.0, 20.0, Vec2::new(0.0, 10.0), BodyOptions::new().is_static(true));
+		let mut body = Body::rectangle(20.0, 20.0, Vec2::new(0.0, -9.9), BodyOptions::new().restitution(0.0));
+		let id = body.id;
+		world.add_body(&mut ground);
+		world.add_body(&mut body);
+		bodies.insert(ground.id, ground);
+		bodies.insert(id, body);
+
+		let enabled = Rc::new(Cell::new(true));
+		let callback_enabled = enabled.clone();
+		world.pre_solve = Some(Box::new(move |_| callback_enabled.get()));
+		(world, bodies, id, enabled)
+	}
+
+	fn run(world: &mut World, bodies: &mut BodyMap, enabled: &Cell<bool>, sequence: &[bool]) -> Vec<String> {
+		sequence.iter().map(|step_enabled| {
+			enabled.set(*step_enabled);
+			step(world, bodies)
+		}).collect()
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn disabling_doesnt_end_touching() {
+		let (mut world, mut bodies, id, enabled) = setup();
+		let events = run(&mut world, &mut bodies, &enabled, &[true, true, false, false, true, true]);
+		assert_eq!(events, ["B", "P", "", "", "P", "P"]);
+
+		bodies.get_mut(&id).unwrap().set_position(Vec2::new(0.0, -500.0));
+		assert_eq!(step(&mut world, &mut bodies), "E");
+	}
+
+	#[test]
+	fn disabled_from_the_start_begins_when_enabled() {
+		let (mut world, mut bodies, _, enabled) = setup();
+		let events = run(&mut world, &mut bodies, &enabled, &[false, false, true, true]);
+		assert_eq!(events, ["", "", "B", "P"]);
+	}
+
+	#[test]
+	fn ends_while_disabled() {
+		let (mut world, mut bodies, id, enabled) = setup();
+		let events = run(&mut world, &mut bodies, &enabled, &[true, true, false]);
+		assert_eq!(events, ["B", "P", ""]);
+
+		bodies.get_mut(&id).unwrap().set_position(Vec2::new(0.0, -500.0));
+		assert_eq!(step(&mut world, &mut bodies), "E");
+		assert_eq!(step(&mut world, &mut bodies), "");
+
+		// never enabled, so it never began and doesn't end either
+		let (mut world, mut bodies, id, enabled) = setup();
+		let events = run(&mut world, &mut bodies, &enabled, &[false, false]);
+		assert_eq!(events, ["", ""]);
+		bodies.get_mut(&id).unwrap().set_position(Vec2::new(0.0, -500.0));
+		assert_eq!(step(&mut world, &mut bodies), "");
+	}
+
+	#[test]
+	fn speculative_pairs_can_be_disabled() {
+		// Box heading at a wall it'll reach this step, which a speculative contact would slow down before it touches
+		let approach = |enabled: bool| {
+			let mut world = World::from_options(&EngineOptions::new());
+			world.gravity = Vec2::zero();
+			world.speculative_contacts = true;
+			let mut bodies = BodyMap::default();
+			let mut wall = Body::rectangle(20.0, 200.0, Vec2::new(50.0, 0.0), BodyOptions::new().is_static(true));
+			let mut body = Body::rectangle(20.0, 20.0, Vec2::new(0.0, 0.0), BodyOptions::new());
+			body.set_velocity(Vec2::new(3600.0, 0.0));
+			let id = body.id;
+			world.add_body(&mut wall);
+			world.add_body(&mut body);
+			bodies.insert(wall.id, wall);
+			bodies.insert(id, body);
+			world.pre_solve = Some(Box::new(move |_| enabled));
+
+			physics::update(&mut world, &mut bodies, 1.0 / 60.0);
+			bodies[&id].get_velocity().x
+		};
+		assert!(approach(true) < 3600.0, "speculative contact should slow the box down");
+		assert_eq!(approach(false), 3600.0, "disabled speculative contact shouldn't touch the box");
+	}
+}
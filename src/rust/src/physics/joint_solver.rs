@@ -0,0 +1,333 @@
+use crate::{Body, BodyMap, Event, Geo, Id, Joint, JointKind, Time, Vec2, World};
+
+// Fraction of a joint's position error that's corrected each step
+const BAUMGARTE: Geo = 0.2;
+
+// How fast a scalar constraint changes with one body's velocity and angular velocity
+struct JacobianRow {
+	body_id: Id,
+	linear: Vec2,
+	angular: Geo,
+}
+
+// Clears impulses accumulated during the last step
+pub fn prepare(world: &mut World) {
+	for joint in world.joints.values_mut() {
+		joint.reset_impulses();
+	}
+}
+
+// delta is the time for the whole step, not one iteration like the contact solver's. Every iteration aims the velocity at the same target,
+// and positions only move once after all of them, so the bias that pulls a joint back by BAUMGARTE of its error is spread over the whole step
+// With the iteration's time the bias is velocity_iterations times too strong, and long or heavy chains overshoot further every step until they blow up
+pub fn solve_velocity(world: &mut World, bodies: &mut BodyMap, delta: Time) {
+	/*
+		For all joints
+			Get relative velocity along the constrained directions
+			Find impulse that removes it, plus a bias that pulls the joint back into place
+			Accumulate and clamp impulse, then apply it to the bodies
+	*/
+	let joint_ids: Vec<Id> = world.joints.keys().copied().collect();
+	for joint_id in joint_ids {
+		let joint = world.joints.get(&joint_id).unwrap(); // unwrap ok, ids were just collected
+		if !world.bodies.contains(&joint.body_a) || !world.bodies.contains(&joint.body_b) { continue; } // body isn't in the world
+
+		// Gears need to read the joints they couple, so they're solved before taking a mutable borrow
+		if let JointKind::Gear { joint_a, joint_b, ratio, constant } = joint.kind {
+			let (Some(joint_a), Some(joint_b)) = (world.joints.get(&joint_a), world.joints.get(&joint_b)) else { continue }; // coupled joint was removed
+			let Some(lambda) = solve_gear(bodies, joint_a, joint_b, ratio, constant, delta) else { continue };
+			world.joints.get_mut(&joint_id).unwrap().axial_impulse += lambda;
+			continue;
+		}
+
+		let joint = world.joints.get_mut(&joint_id).unwrap();
+		match joint.kind {
+			JointKind::Revolute => solve_revolute(bodies, joint, delta),
+			JointKind::Prismatic { axis, reference_angle } => solve_prismatic(bodies, joint, &axis, reference_angle, delta),
+			JointKind::Rope { max_length } => solve_rope(bodies, joint, max_length, delta),
+			JointKind::Pulley { ground_a, ground_b, ratio, length } => solve_pulley(bodies, joint, &ground_a, &ground_b, ratio, length, delta),
+			JointKind::Gear { .. } => unreachable!(),
+		}
+	}
+}
+
+// Removes joints whose reaction over the step was past their break thresholds, queueing an event for each
+pub fn break_joints(world: &mut World, delta: Time) {
+	let broken: Vec<Id> = world.joints.values()
+		.filter(|joint| joint.should_break(delta))
+		.map(|joint| joint.id)
+		.collect();
+
+	for joint_id in broken {
+		let joint = world.remove_joint(joint_id).unwrap(); // unwrap ok, id was just collected
+		world.events.push(Event::JointBreak { // always queued, breaks are rare and there's no other way to find out about them
+			joint: joint.id,
+			body_a: joint.body_a,
+			body_b: joint.body_b,
+			force: joint.get_reaction_force(delta),
+			torque: joint.get_reaction_torque(delta),
+		});
+	}
+}
+
+fn solve_revolute(bodies: &mut BodyMap, joint: &mut Joint, delta: Time) {
+	let body_a = bodies.get(&joint.body_a).expect("failed to get body_a in joint_solver::solve_revolute");
+	let body_b = bodies.get(&joint.body_b).expect("failed to get body_b in joint_solver::solve_revolute");
+
+	let (ra, rb) = joint.get_world_radii(body_a, body_b);
+	let (ma, ia) = get_inverse_mass(body_a);
+	let (mb, ib) = get_inverse_mass(body_b);
+
+	let vra = body_a.velocity + ra.cross_float(body_a.angular_velocity); // velocity of anchor a
+	let vrb = body_b.velocity + rb.cross_float(body_b.angular_velocity); // velocity of anchor b
+	let vr = vrb - vra; // relative velocity between anchors
+	let error = (body_b.position + rb) - (body_a.position + ra); // anchor a to anchor b
+
+	// Point constraint; solve the 2x2 effective mass matrix K * impulse = -(vr + bias)
+	let k11 = ma.x + mb.x + ia * ra.y * ra.y + ib * rb.y * rb.y;
+	let k12 = -ia * ra.x * ra.y - ib * rb.x * rb.y;
+	let k22 = ma.y + mb.y + ia * ra.x * ra.x + ib * rb.x * rb.x;
+	let det = k11 * k22 - k12 * k12;
+	if det == 0.0 { return; }
+
+	let rhs = -(vr + error * (BAUMGARTE / delta));
+	let impulse = Vec2::new(k22 * rhs.x - k12 * rhs.y, k11 * rhs.y - k12 * rhs.x) / det;
+	joint.impulse += impulse;
+
+	apply_impulse(bodies, joint.body_a, &-impulse, &ra);
+	apply_impulse(bodies, joint.body_b, &impulse, &rb);
+}
+
+fn solve_prismatic(bodies: &mut BodyMap, joint: &mut Joint, axis: &Vec2, reference_angle: Geo, delta: Time) {
+	let body_a = bodies.get(&joint.body_a).expect("failed to get body_a in joint_solver::solve_prismatic");
+	let body_b = bodies.get(&joint.body_b).expect("failed to get body_b in joint_solver::solve_prismatic");
+
+	let (ra, rb) = joint.get_world_radii(body_a, body_b);
+	let error = (body_b.position + rb) - (body_a.position + ra);
+	let perpendicular = axis.rotate(body_a.angle).normal();
+
+	// Keep anchor b on the axis
+	let rows = [
+		JacobianRow { body_id: joint.body_a, linear: -perpendicular, angular: -(ra + error).cross(&perpendicular) },
+		JacobianRow { body_id: joint.body_b, linear: perpendicular, angular: rb.cross(&perpendicular) },
+	];
+	let bias = perpendicular.dot(&error) * BAUMGARTE / delta;
+	if let Some(lambda) = solve_rows(bodies, &rows, bias) {
+		apply_rows(bodies, &rows, lambda);
+		joint.impulse += perpendicular * lambda;
+	}
+
+	// Keep the relative angle fixed
+	let body_a = bodies.get(&joint.body_a).unwrap(); // unwrap ok, already fetched above
+	let body_b = bodies.get(&joint.body_b).unwrap();
+	let rows = [
+		JacobianRow { body_id: joint.body_a, linear: Vec2::zero(), angular: -1.0 },
+		JacobianRow { body_id: joint.body_b, linear: Vec2::zero(), angular: 1.0 },
+	];
+	let bias = (body_b.angle - body_a.angle - reference_angle) * BAUMGARTE / delta;
+	if let Some(lambda) = solve_rows(bodies, &rows, bias) {
+		apply_rows(bodies, &rows, lambda);
+		joint.angular_impulse += lambda;
+	}
+}
+
+fn solve_rope(bodies: &mut BodyMap, joint: &mut Joint, max_length: Geo, delta: Time) {
+	let body_a = bodies.get(&joint.body_a).expect("failed to get body_a in joint_solver::solve_rope");
+	let body_b = bodies.get(&joint.body_b).expect("failed to get body_b in joint_solver::solve_rope");
+
+	let (ra, rb) = joint.get_world_radii(body_a, body_b);
+	let error = (body_b.position + rb) - (body_a.position + ra);
+	let length = error.length();
+	if length <= Geo::EPSILON { return; } // no direction to pull in
+	let normal = error / length;
+
+	let rows = [
+		JacobianRow { body_id: joint.body_a, linear: -normal, angular: -ra.cross(&normal) },
+		JacobianRow { body_id: joint.body_b, linear: normal, angular: rb.cross(&normal) },
+	];
+	let bias = get_inequality_bias(length - max_length, delta);
+	let Some(lambda) = solve_rows(bodies, &rows, bias) else { return };
+
+	let old_impulse = joint.axial_impulse;
+	joint.axial_impulse = (old_impulse + lambda).min(0.0); // rope can only pull
+	apply_rows(bodies, &rows, joint.axial_impulse - old_impulse);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn solve_pulley(bodies: &mut BodyMap, joint: &mut Joint, ground_a: &Vec2, ground_b: &Vec2, ratio: Geo, length: Geo, delta: Time) {
+	let body_a = bodies.get(&joint.body_a).expect("failed to get body_a in joint_solver::solve_pulley");
+	let body_b = bodies.get(&joint.body_b).expect("failed to get body_b in joint_solver::solve_pulley");
+
+	let (ra, rb) = joint.get_world_radii(body_a, body_b);
+	let rope_a = body_a.position + ra - ground_a;
+	let rope_b = body_b.position + rb - ground_b;
+	let (length_a, length_b) = (rope_a.length(), rope_b.length());
+	if length_a <= Geo::EPSILON || length_b <= Geo::EPSILON { return; } // no direction to pull in
+	let ua = rope_a / length_a;
+	let ub = rope_b / length_b;
+
+	let rows = [
+		JacobianRow { body_id: joint.body_a, linear: ua, angular: ra.cross(&ua) },
+		JacobianRow { body_id: joint.body_b, linear: ub * ratio, angular: rb.cross(&ub) * ratio },
+	];
+	let bias = (length_a + ratio * length_b - length) * BAUMGARTE / delta; // kept exactly at length, the rope never goes slack
+	let Some(lambda) = solve_rows(bodies, &rows, bias) else { return };
+	joint.axial_impulse += lambda;
+	apply_rows(bodies, &rows, lambda);
+}
+
+// Returns the impulse applied
+fn solve_gear(bodies: &mut BodyMap, joint_a: &Joint, joint_b: &Joint, ratio: Geo, constant: Geo, delta: Time) -> Option<Geo> {
+	let coordinate = joint_a.get_coordinate(bodies)? + ratio * joint_b.get_coordinate(bodies)?;
+	let mut rows = get_coordinate_rows(bodies, joint_a, 1.0)?;
+	rows.extend(get_coordinate_rows(bodies, joint_b, ratio)?);
+
+	let bias = (coordinate - constant) * BAUMGARTE / delta;
+	let lambda = solve_rows(bodies, &rows, bias)?;
+	apply_rows(bodies, &rows, lambda);
+	Some(lambda)
+}
+
+// Jacobian of a revolute or prismatic joint's coordinate (see Joint::get_coordinate), scaled by scale
+fn get_coordinate_rows(bodies: &BodyMap, joint: &Joint, scale: Geo) -> Option<Vec<JacobianRow>> {
+	let body_a = bodies.get(&joint.body_a)?;
+	let body_b = bodies.get(&joint.body_b)?;
+	match joint.kind {
+		JointKind::Revolute => Some(vec![
+			JacobianRow { body_id: joint.body_a, linear: Vec2::zero(), angular: -scale },
+			JacobianRow { body_id: joint.body_b, linear: Vec2::zero(), angular: scale },
+		]),
+		JointKind::Prismatic { axis, .. } => {
+			let (ra, rb) = joint.get_world_radii(body_a, body_b);
+			let error = (body_b.position + rb) - (body_a.position + ra);
+			let axis = axis.rotate(body_a.angle);
+			Some(vec![
+				JacobianRow { body_id: joint.body_a, linear: -axis * scale, angular: -(ra + error).cross(&axis) * scale },
+				JacobianRow { body_id: joint.body_b, linear: axis * scale, angular: rb.cross(&axis) * scale },
+			])
+		},
+		_ => None,
+	}
+}
+
+// Bias for constraints that only push one way: let the gap close within one step, but only correct part of the error once it's violated
+fn get_inequality_bias(separation: Geo, delta: Time) -> Geo {
+	if separation < 0.0 { separation / delta } else { separation * BAUMGARTE / delta }
+}
+
+// Finds the unclamped impulse that brings the constraint's velocity to -bias. Returns None if no body can move
+fn solve_rows(bodies: &BodyMap, rows: &[JacobianRow], bias: Geo) -> Option<Geo> {
+	let mut velocity = 0.0;
+	let mut effective_mass = 0.0;
+	for (i, row) in rows.iter().enumerate() {
+		let body = bodies.get(&row.body_id).expect("failed to get body in joint_solver::solve_rows");
+		let (m, i_inv) = get_inverse_mass(body);
+		velocity += row.linear.dot(&body.velocity) + row.angular * body.angular_velocity;
+
+		// Rows for the same body have to be combined, otherwise their cross terms are missed
+		if rows[..i].iter().any(|other| other.body_id == row.body_id) { continue; }
+		let (linear, angular) = rows[i..].iter()
+			.filter(|other| other.body_id == row.body_id)
+			.fold((Vec2::zero(), 0.0), |(linear, angular), other| (linear + other.linear, angular + other.angular));
+		effective_mass += (linear * m).dot(&linear) + i_inv * angular * angular;
+	}
+	if effective_mass <= 0.0 { return None; }
+
+	Some(-(velocity + bias) / effective_mass)
+}
+
+fn apply_rows(bodies: &mut BodyMap, rows: &[JacobianRow], lambda: Geo) {
+	for row in rows.iter() {
+		let body = bodies.get_mut(&row.body_id).expect("failed to get body in joint_solver::apply_rows");
+		if body.is_static { continue; }
+		body.velocity += row.linear * body.get_axis_inverse_mass() * lambda;
+		body.angular_velocity += row.angular * body.get_rotational_inverse_inertia() * lambda;
+	}
+}
+
+// Inverse mass per axis and inertia, treating static bodies and locked axes as immovable
+fn get_inverse_mass(body: &Body) -> (Vec2, Geo) {
+	if body.is_static { (Vec2::zero(), 0.0) } else { (body.get_axis_inverse_mass(), body.get_rotational_inverse_inertia()) }
+}
+
+fn apply_impulse(bodies: &mut BodyMap, body_id: Id, impulse: &Vec2, radius: &Vec2) {
+	let body = bodies.get_mut(&body_id).expect("failed to get body in joint_solver::apply_impulse");
+	if body.is_static { return; }
+	body.velocity += impulse * body.get_axis_inverse_mass();
+	body.angular_velocity += radius.cross(impulse) * body.get_rotational_inverse_inertia();
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{Body, BodyMap, BodyOptions, EngineOptions, Event, Geo, Joint, Vec2, World, physics};
+
+	#[test]
+	fn pulley_stays_taut() {
+		let mut world = World::from_options(&EngineOptions::new());
+		let mut bodies = BodyMap::default();
+		let (ground_a, ground_b) = (Vec2::new(0.0, 0.0), Vec2::new(100.0, 0.0));
+		let mut body_a = Body::rectangle(20.0, 20.0, Vec2::new(0.0, 100.0), BodyOptions::new());
+		let mut body_b = Body::rectangle(20.0, 20.0, Vec2::new(100.0, 100.0), BodyOptions::new());
+		let (id_a, id_b) = (body_a.id, body_b.id);
+		let joint = Joint::pulley(&body_a, *body_a.get_position(), &body_b, *body_b.get_position(), ground_a, ground_b, 2.0);
+		world.add_body(&mut body_a);
+		world.add_body(&mut body_b);
+		bodies.insert(id_a, body_a);
+		bodies.insert(id_b, body_b);
+		world.add_joint(joint);
+
+		// Throwing a up would let the rope go slack if it could only pull
+		bodies.get_mut(&id_a).unwrap().set_velocity(Vec2::new(0.0, -150.0));
+		for _ in 0..60 {
+			physics::update(&mut world, &mut bodies, 1.0 / 60.0);
+			let length = (bodies[&id_a].get_position() - ground_a).length() + 2.0 * (bodies[&id_b].get_position() - ground_b).length();
+			assert!((length - 300.0).abs() < 1.0, "rope length should stay 300, was {}", length);
+		}
+	}
+
+	#[test]
+	fn joint_breaks_are_queued_without_recording() {
+		let mut world = World::from_options(&EngineOptions::new());
+		let mut bodies = BodyMap::default();
+		let mut ground = Body::rectangle(20.0, 20.0, Vec2::zero(), BodyOptions::new().is_static(true));
+		let mut body = Body::rectangle(20.0, 20.0, Vec2::new(40.0, 0.0), BodyOptions::new());
+		let joint = Joint::revolute(&ground, &body, Vec2::new(20.0, 0.0)).break_force(Some(1.0));
+		let joint_id = joint.id;
+		world.add_body(&mut ground);
+		world.add_body(&mut body);
+		bodies.insert(ground.id, ground);
+		bodies.insert(body.id, body);
+		world.add_joint(joint);
+
+		physics::update(&mut world, &mut bodies, 1.0 / 60.0);
+		assert!(!world.record_events);
+		assert!(world.joints.is_empty(), "gravity should break the joint");
+		assert!(matches!(world.events.as_slice(), [Event::JointBreak { joint, .. }] if *joint == joint_id));
+	}
+
+	#[test]
+	fn heavy_chain_stays_stable() {
+		// Links swinging down from horizontal with a heavy weight on the end, which overshoots if the bias is too strong
+		let mut world = World::from_options(&EngineOptions::new());
+		let mut bodies = BodyMap::default();
+		let mut ground = Body::rectangle(10.0, 10.0, Vec2::zero(), BodyOptions::new().is_static(true));
+		let mut prev_id = ground.id;
+		world.add_body(&mut ground);
+		bodies.insert(ground.id, ground);
+		for i in 1..=12 {
+			let mass = if i == 12 { 50.0 } else { 1.0 };
+			let mut link = Body::rectangle(18.0, 6.0, Vec2::new(i as Geo * 20.0, 0.0), BodyOptions::new().mass(mass));
+			world.add_joint(Joint::revolute(&bodies[&prev_id], &link, Vec2::new(i as Geo * 20.0 - 10.0, 0.0)));
+			world.add_body(&mut link);
+			prev_id = link.id;
+			bodies.insert(link.id, link);
+		}
+
+		for _ in 0..600 {
+			physics::update(&mut world, &mut bodies, 1.0 / 60.0);
+			for body in bodies.values() {
+				assert!(body.get_velocity().length() < 2000.0, "chain blew up, a link is moving at {}", body.get_velocity().length());
+			}
+		}
+	}
+}
@@ -1,6 +1,7 @@
 use crate::{BodyMap, World, Time};
 
-pub fn solve_velocity(world: &mut World, bodies: &mut BodyMap, delta: Time) {
+// delta is the time for this iteration, step_delta the time for the whole step
+pub fn solve_velocity(world: &mut World, bodies: &mut BodyMap, delta: Time, step_delta: Time) {
 	/*
 		For all collision pairs
 			For all collision contacts
@@ -10,7 +11,7 @@ pub fn solve_velocity(world: &mut World, bodies: &mut BodyMap, delta: Time) {
 	*/
 
 	// Clear old collision pairs
-	world.collision_pairs.retain(|pair| {
+	world.collision_pairs.retain(|_, pair| {
 		if !pair.is_valid(world.frame) {
 			return false;
 		}
@@ -24,15 +25,15 @@ pub fn solve_velocity(world: &mut World, bodies: &mut BodyMap, delta: Time) {
 
 
 		let angle_a = body_a.angle;
-		let ma = body_a.inverse_mass;
-		let ia = body_a.inverse_inertia;
+		let ma = body_a.get_axis_inverse_mass(); // per axis, so locked axes don't move
+		let ia = body_a.get_rotational_inverse_inertia();
 		let pa = &body_a.position;
 		let wa = body_a.angular_velocity;
 		let va = &body_a.velocity;
 		
 		let angle_b = body_b.angle;
-		let mb = body_b.inverse_mass;
-		let ib = body_b.inverse_inertia;
+		let mb = body_b.get_axis_inverse_mass();
+		let ib = body_b.get_rotational_inverse_inertia();
 		let pb = &body_b.position;
 		let wb = body_b.angular_velocity;
 		let vb = &body_b.velocity;
@@ -47,6 +48,7 @@ pub fn solve_velocity(world: &mut World, bodies: &mut BodyMap, delta: Time) {
 
 
 		let slop = 1.0;
+		let mut total_impulse = 0.0;
 		
 		for contact in contacts.iter() {
 			let ra = contact.anchor_a.rotate(angle_a); // contact radius a
@@ -57,13 +59,17 @@ pub fn solve_velocity(world: &mut World, bodies: &mut BodyMap, delta: Time) {
 			let vn = vr.dot(normal); // normal velocity
 			let vt = vr.dot(tangent); // tangent velocity
 			
+			// Speculative contacts only push back on the part of the approach speed that would close the gap this step
+			let speculative_speed = vn + depth / step_delta;
+			if pair.speculative && speculative_speed <= 0.0 { continue; }
+
 			// Adjusted separation
 			let adjusted_separation = depth + ((&rb + pb) - (&ra + pa)).dot(normal);
 
 			// Separation
 			let ds = (&rb + vb) - (va + &ra);
 			let mut s = ds.dot(normal) * delta + adjusted_separation; // separation scalar
-			if s < 0.0 { continue; }
+			if s < 0.0 && !pair.speculative { continue; }
 			s = (s - slop * s.signum()).max(0.0); // allow a little penetration
 			
 			// Baumgarte stabilization
@@ -72,17 +78,22 @@ pub fn solve_velocity(world: &mut World, bodies: &mut BodyMap, delta: Time) {
 			// Normal mass
 			let rna = ra.cross(normal);
 			let rnb = rb.cross(normal);
-			let k_normal = ma + mb + (ia * rna * rna) + (ib * rnb * rnb);
+			let k_normal = (ma + mb).dot(&(normal * normal)) + (ia * rna * rna) + (ib * rnb * rnb);
 			let normal_mass = if k_normal > 0.0 { 1.0 / k_normal } else { 0.0 };
 
 			// Tangent mass
 			let rta = ra.cross(tangent);
 			let rtb = rb.cross(tangent);
-			let k_tangent = ma + mb + (ia * rta * rta) + (ib * rtb * rtb);
+			let k_tangent = (ma + mb).dot(&(tangent * tangent)) + (ia * rta * rta) + (ib * rtb * rtb);
 			let tangent_mass = if k_tangent > 0.0 { 1.0 / k_tangent } else { 0.0 };
 
 			// Impulses
-			let mut normal_impulse = normal_mass * contact.mass_coefficient * (vn * restitution + bias);
+			let mut normal_impulse = if pair.speculative {
+				normal_mass * contact.mass_coefficient * speculative_speed
+			}
+			else {
+				normal_mass * contact.mass_coefficient * (vn * restitution + bias)
+			};
 			let mut tangent_impulse = -vt * tangent_mass;
 			
 			// Clamp impulses
@@ -93,6 +104,7 @@ pub fn solve_velocity(world: &mut World, bodies: &mut BodyMap, delta: Time) {
 			tangent_impulse = tangent_impulse.min(max_tangent_impulse).max(-max_tangent_impulse);
 
 			let p = normal * normal_impulse - tangent * tangent_impulse; // final impulse
+			total_impulse += normal_impulse;
 
 			vfa += &p * ma;
 			wfa += ra.cross(&p) * ia;
@@ -100,6 +112,8 @@ pub fn solve_velocity(world: &mut World, bodies: &mut BodyMap, delta: Time) {
 			wfb -= rb.cross(&p) * ib;
 		}
 
+		pair.impulse += total_impulse;
+
 		// update bodies
 		let body_a = bodies.get_mut(&pair.body_a).expect("failed to get body_a in solver::solve_velocity");
 		if !body_a.is_static {
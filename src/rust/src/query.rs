@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsValue;
+
+use crate::{Body, Bounds, Id, World};
+
+mod raycast;
+pub use raycast::{RaycastHit, raycast, raycast_all};
+
+mod region;
+pub use region::{query_point, query_aabb, overlap_shape};
+
+mod shape_cast;
+pub use shape_cast::{Impact, ShapeCastHit, shape_cast, time_of_impact};
+
+mod distance;
+pub use distance::{Distance, body_distance};
+
+#[cfg(test)]
+mod test_helpers;
+
+// Ids of bodies that might overlap bounds; from the broadphase, or every body in the world if that's faster
+pub fn get_candidates(world: &World, bounds: &Bounds) -> Vec<Id> {
+	// Walking more grid buckets than there are bodies is slower than checking every body
+	if world.broadphase.as_grid().is_some_and(|grid| grid.count_buckets(bounds) > world.bodies.len() as u64) {
+		world.bodies.iter().copied().collect()
+	}
+	else {
+		world.broadphase.query_aabb(bounds)
+	}
+}
+
+// Which bodies a query is allowed to return
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct QueryFilter {
+	pub exclude: Vec<Id>,
+	pub include_static: bool,
+	pub include_dynamic: bool,
+}
+
+impl QueryFilter {
+	pub fn allows(&self, body: &Body) -> bool {
+		let allowed = if body.is_static { self.include_static } else { self.include_dynamic };
+		allowed && !self.exclude.contains(&body.id)
+	}
+}
+
+impl Default for QueryFilter {
+	fn default() -> Self {
+		Self {
+			exclude: Vec::new(),
+			include_static: true,
+			include_dynamic: true,
+		}
+	}
+}
+
+impl From<JsValue> for QueryFilter {
+	fn from(value: JsValue) -> QueryFilter {
+		if value.is_null_or_undefined() {
+			return QueryFilter::default();
+		}
+
+		serde_wasm_bindgen::from_value(value)
+			.unwrap_or_else(|error| {
+				web_sys::console::warn_1(&format!("Failed to parse QueryFilter: {}. Using defaults.", error).into());
+				QueryFilter::default()
+			})
+	}
+}
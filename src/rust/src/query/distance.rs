@@ -0,0 +1,281 @@
+use serde::Serialize;
+
+use crate::{Body, Geo, Vec2};
+
+const MAX_ITERATIONS: usize = 32;
+const TOLERANCE: Geo = 0.0001;
+
+#[derive(Serialize, Clone)]
+pub struct Distance {
+	pub distance: Geo, // negative when the bodies overlap, by how far they're overlapping
+	pub point_a: Vec2, // closest point on a to b, or deepest point of a inside b when overlapping
+	pub point_b: Vec2, // closest point on b to a, or deepest point of b inside a when overlapping
+	pub normal: Vec2, // direction from a toward b. Moving a by normal * distance when overlapping separates them
+}
+
+// Point of the Minkowski difference a - b, with the vertices of a and b it came from
+#[derive(Clone, Copy)]
+struct SupportPoint {
+	a: Vec2,
+	b: Vec2,
+	point: Vec2,
+}
+
+pub fn body_distance(body_a: &Body, body_b: &Body) -> Distance {
+	distance(body_a.get_vertices(), body_b.get_vertices())
+}
+
+// GJK: distance and closest points between two convex polygons
+// Falls back to EPA for the penetration depth and normal if they overlap
+pub fn distance(a: &[Vec2], b: &[Vec2]) -> Distance {
+	let mut direction = a[0] - b[0];
+	if direction.length() == 0.0 { direction = Vec2::new(1.0, 0.0); }
+	let mut simplex = vec![support(a, b, &-direction)];
+
+	for _ in 0..MAX_ITERATIONS {
+		let (closest, weights) = reduce_simplex(&mut simplex);
+		if simplex.len() == 3 { // origin is inside the simplex
+			return penetration(a, b, simplex);
+		}
+
+		let length = closest.length();
+		if length < TOLERANCE { // origin is on the simplex, either touching or overlapping
+			if expand_simplex(a, b, &mut simplex) {
+				return penetration(a, b, simplex);
+			}
+			return touching(a, b, &simplex, &weights);
+		}
+
+		// Stop once the support point in the direction of the origin doesn't get any closer
+		let next = support(a, b, &-closest);
+		let progress = closest.dot(&closest) - closest.dot(&next.point);
+		if progress <= TOLERANCE * length || simplex.iter().any(|s| s.point == next.point) {
+			return separated(&simplex, &weights);
+		}
+		simplex.push(next);
+	}
+
+	let (_, weights) = reduce_simplex(&mut simplex);
+	separated(&simplex, &weights)
+}
+
+fn support(a: &[Vec2], b: &[Vec2], direction: &Vec2) -> SupportPoint {
+	let furthest = |vertices: &[Vec2], direction: &Vec2| {
+		*vertices.iter().max_by(|x, y| x.dot(direction).total_cmp(&y.dot(direction))).unwrap() // unwrap ok, polygons aren't empty
+	};
+	let a = furthest(a, direction);
+	let b = furthest(b, &-direction);
+	SupportPoint { a, b, point: a - b }
+}
+
+// Finds the point on the simplex closest to the origin, and removes the points that don't contribute to it
+// Returns the closest point and the weights of the remaining simplex points. Leaves all 3 points if the origin is inside
+fn reduce_simplex(simplex: &mut Vec<SupportPoint>) -> (Vec2, Vec<Geo>) {
+	match simplex.len() {
+		1 => (simplex[0].point, vec![1.0]),
+		2 => {
+			let (closest, t) = closest_on_segment(&simplex[0].point, &simplex[1].point);
+			if t <= 0.0 {
+				simplex.truncate(1);
+				(closest, vec![1.0])
+			}
+			else if t >= 1.0 {
+				simplex.remove(0);
+				(closest, vec![1.0])
+			}
+			else {
+				(closest, vec![1.0 - t, t])
+			}
+		},
+		_ => {
+			let [p0, p1, p2] = [simplex[0].point, simplex[1].point, simplex[2].point];
+			let area = (p1 - p0).cross(&(p2 - p0));
+			let inside = [(p0, p1), (p1, p2), (p2, p0)].iter()
+				.all(|(cur, next)| (next - cur).cross(&-*cur) * area >= 0.0);
+			if inside && area != 0.0 {
+				return (Vec2::zero(), vec![1.0 / 3.0; 3]);
+			}
+
+			// Closest point is on one of the edges
+			let mut best: Option<(usize, Vec2, Geo)> = None;
+			for i in 0..3 {
+				let (closest, t) = closest_on_segment(&simplex[i].point, &simplex[(i + 1) % 3].point);
+				if best.is_none_or(|(_, best, _)| closest.length() < best.length()) {
+					best = Some((i, closest, t));
+				}
+			}
+			let (i, _, _) = best.unwrap(); // unwrap ok, there are 3 edges
+			let edge = vec![simplex[i], simplex[(i + 1) % 3]];
+			*simplex = edge;
+			reduce_simplex(simplex)
+		},
+	}
+}
+
+// Closest point to the origin on the segment from start to end, and how far along the segment it is, 0 to 1
+fn closest_on_segment(start: &Vec2, end: &Vec2) -> (Vec2, Geo) {
+	let edge = end - start;
+	let length_squared = edge.dot(&edge);
+	if length_squared == 0.0 { return (*start, 0.0); }
+	let t = (-start.dot(&edge) / length_squared).clamp(0.0, 1.0);
+	(start + edge * t, t)
+}
+
+// Tries to grow a point or segment through the origin into a triangle, so EPA can start from it
+// Fails if the Minkowski difference has no area on that side, meaning the polygons are only touching
+fn expand_simplex(a: &[Vec2], b: &[Vec2], simplex: &mut Vec<SupportPoint>) -> bool {
+	if simplex.len() == 1 {
+		let Some(next) = [Vec2::new(1.0, 0.0), Vec2::new(-1.0, 0.0), Vec2::new(0.0, 1.0), Vec2::new(0.0, -1.0)].iter()
+			.map(|direction| support(a, b, direction))
+			.find(|next| (next.point - simplex[0].point).length() > TOLERANCE)
+			else { return false };
+		simplex.push(next);
+	}
+
+	let normal = (simplex[1].point - simplex[0].point).normal().normalize();
+	let Some(next) = [normal, -normal].iter()
+		.map(|direction| (support(a, b, direction), direction))
+		.find(|(next, direction)| next.point.dot(direction) > TOLERANCE)
+		.map(|(next, _)| next)
+		else { return false };
+	simplex.push(next);
+	true
+}
+
+// Points on a and b from the weighted simplex points
+fn get_witness_points(simplex: &[SupportPoint], weights: &[Geo]) -> (Vec2, Vec2) {
+	simplex.iter().zip(weights).fold((Vec2::zero(), Vec2::zero()), |(a, b), (s, weight)| {
+		(a + s.a * *weight, b + s.b * *weight)
+	})
+}
+
+fn separated(simplex: &[SupportPoint], weights: &[Geo]) -> Distance {
+	let (point_a, point_b) = get_witness_points(simplex, weights);
+	let difference = point_b - point_a;
+	let distance = difference.length();
+	Distance {
+		distance,
+		point_a,
+		point_b,
+		normal: if distance > 0.0 { difference / distance } else { Vec2::zero() },
+	}
+}
+
+fn touching(a: &[Vec2], b: &[Vec2], simplex: &[SupportPoint], weights: &[Geo]) -> Distance {
+	let (point_a, point_b) = get_witness_points(simplex, weights);
+	// Normal is perpendicular to the touching edge, or between the centers if only corners touch, facing from a to b
+	let centers = get_center(b) - get_center(a);
+	let normal = if simplex.len() == 2 {
+		let normal = (simplex[1].point - simplex[0].point).normal().normalize();
+		if normal.dot(&centers) < 0.0 { -normal } else { normal }
+	}
+	else {
+		centers.normalize()
+	};
+	Distance { distance: 0.0, point_a, point_b, normal }
+}
+
+fn get_center(vertices: &[Vec2]) -> Vec2 {
+	vertices.iter().fold(Vec2::zero(), |sum, vertex| sum + vertex) / vertices.len() as Geo
+}
+
+// EPA: expands the simplex out to the edge of the Minkowski difference closest to the origin
+fn penetration(a: &[Vec2], b: &[Vec2], mut polygon: Vec<SupportPoint>) -> Distance {
+	// Outward normals depend on the winding of the polygon, which stays the same as points are added
+	let winding = if (polygon[1].point - polygon[0].point).cross(&(polygon[2].point - polygon[0].point)) > 0.0 { 1.0 } else { -1.0 };
+
+	let mut closest = (0, Vec2::zero(), 0.0); // edge index, normal, distance from origin
+	for _ in 0..MAX_ITERATIONS {
+		closest = (0, Vec2::zero(), Geo::MAX);
+		for i in 0..polygon.len() {
+			let edge = polygon[(i + 1) % polygon.len()].point - polygon[i].point;
+			if edge.length() == 0.0 { continue; }
+			let normal = edge.normal().normalize() * winding;
+			let distance = normal.dot(&polygon[i].point);
+			if distance < closest.2 {
+				closest = (i, normal, distance);
+			}
+		}
+
+		let (i, normal, distance) = closest;
+		let next = support(a, b, &normal);
+		if next.point.dot(&normal) - distance < TOLERANCE || polygon.iter().any(|s| s.point == next.point) {
+			break;
+		}
+		polygon.insert(i + 1, next);
+	}
+
+	// Project the origin onto the closest edge to find the deepest points
+	let (i, normal, depth) = closest;
+	let start = polygon[i];
+	let end = polygon[(i + 1) % polygon.len()];
+	let (_, t) = closest_on_segment(&start.point, &end.point);
+	let (point_a, point_b) = get_witness_points(&[start, end], &[1.0 - t, t]);
+	Distance {
+		distance: -depth,
+		point_a,
+		point_b,
+		normal,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{BodyOptions, query::test_helpers::{assert_close, square}};
+
+	#[test]
+	fn separated() {
+		let result = body_distance(&square(0.0, 0.0), &square(40.0, 0.0));
+		assert_close(result.distance, 20.0);
+		assert_close(result.normal.x, 1.0);
+		assert_close(result.normal.y, 0.0);
+		assert_close(result.point_a.x, 10.0);
+		assert_close(result.point_b.x, 30.0);
+	}
+
+	#[test]
+	fn separated_corners() {
+		// Closest features are the corners (10, 10) and (19, 22), 9 and 12 apart
+		let result = body_distance(&square(0.0, 0.0), &square(29.0, 32.0));
+		assert_close(result.distance, 15.0);
+		assert_close(result.point_a.x, 10.0);
+		assert_close(result.point_a.y, 10.0);
+		assert_close(result.point_b.x, 19.0);
+		assert_close(result.point_b.y, 22.0);
+		assert_close(result.normal.x, 0.6);
+		assert_close(result.normal.y, 0.8);
+	}
+
+	#[test]
+	fn overlapping() {
+		let result = body_distance(&square(0.0, 0.0), &square(17.0, 0.0));
+		assert_close(result.distance, -3.0);
+		assert_close(result.normal.x, 1.0);
+		assert_close(result.normal.y, 0.0);
+	}
+
+	#[test]
+	fn touching() {
+		let result = body_distance(&square(0.0, 0.0), &square(20.0, 0.0));
+		assert_close(result.distance, 0.0);
+	}
+
+	#[test]
+	fn identical() {
+		let result = body_distance(&square(0.0, 0.0), &square(0.0, 0.0));
+		assert_close(result.distance, -20.0);
+		assert_close(result.normal.length(), 1.0);
+	}
+
+	#[test]
+	fn symmetric() {
+		let a = square(0.0, 0.0);
+		let b = Body::rectangle(10.0, 50.0, Vec2::new(-31.0, 12.0), BodyOptions::default());
+		let ab = body_distance(&a, &b);
+		let ba = body_distance(&b, &a);
+		assert_close(ab.distance, 16.0);
+		assert_close(ab.distance, ba.distance);
+		assert_close(ab.normal.x, -ba.normal.x);
+	}
+}
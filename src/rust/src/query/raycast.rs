@@ -0,0 +1,90 @@
+use std::collections::HashSet;
+use serde::Serialize;
+
+use crate::{BodyMap, Geo, Id, Vec2, World, query::QueryFilter};
+
+#[derive(Serialize, Clone)]
+pub struct RaycastHit {
+	pub body: Id,
+	pub point: Vec2,
+	pub normal: Vec2, // surface normal of the body at point
+	pub distance: Geo,
+	pub fraction: Geo, // distance / max_distance
+}
+
+// First body the ray hits within max_distance
+pub fn raycast(world: &World, bodies: &BodyMap, origin: &Vec2, direction: &Vec2, max_distance: Geo, filter: &QueryFilter) -> Option<RaycastHit> {
+	cast(world, bodies, origin, direction, max_distance, filter, true).pop()
+}
+
+// Every body the ray hits within max_distance, closest first
+pub fn raycast_all(world: &World, bodies: &BodyMap, origin: &Vec2, direction: &Vec2, max_distance: Geo, filter: &QueryFilter) -> Vec<RaycastHit> {
+	let mut hits = cast(world, bodies, origin, direction, max_distance, filter, false);
+	hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+	hits
+}
+
+fn cast(world: &World, bodies: &BodyMap, origin: &Vec2, direction: &Vec2, max_distance: Geo, filter: &QueryFilter, first_only: bool) -> Vec<RaycastHit> {
+	let mut hits: Vec<RaycastHit> = Vec::new();
+	let length = direction.length();
+	if length == 0.0 || max_distance <= 0.0 { return hits; }
+	let direction = direction / length;
+
+	let mut tested: HashSet<Id> = HashSet::new();
+	let mut test = |body_id: &Id, hits: &mut Vec<RaycastHit>| {
+		if !tested.insert(*body_id) { return; } // bodies can be in more than one bucket
+		let body = bodies.get(body_id).expect("failed to get body in query::raycast");
+		if !filter.allows(body) { return; }
+		let Some((distance, normal)) = body.raycast(origin, &direction, max_distance) else { return };
+
+		let hit = RaycastHit {
+			body: *body_id,
+			point: origin + direction * distance,
+			normal,
+			distance,
+			fraction: distance / max_distance,
+		};
+		if !first_only {
+			hits.push(hit);
+		}
+		else if hits.first().is_none_or(|best| distance < best.distance) {
+			hits.clear();
+			hits.push(hit);
+		}
+	};
+
+	// Part of the ray to hand to the broadphase
+	let mut start: Geo = 0.0;
+	let mut end = max_distance;
+	if let Some(grid) = world.broadphase.as_grid() {
+		// The grid is walked bucket by bucket, which is slow for long rays and never finishes for huge ones, since the distances
+		// stop changing when a bucket is added to them. Only walk the part that's inside the bodies' bounds, and if that's still more
+		// buckets than there are bodies, test every body instead, same as query::get_candidates
+		let body_count = world.bodies.len() as u64;
+		if !max_distance.is_finite() || grid.count_ray_buckets(&direction, max_distance) > body_count {
+			let Some(bounds) = world.bodies.iter()
+				.map(|body_id| bodies.get(body_id).expect("failed to get body in query::raycast").bounds.clone())
+				.reduce(|bounds, other| bounds.union(&other))
+				else { return hits }; // no bodies
+			let Some((enter, exit)) = bounds.raycast(origin, &direction, max_distance) else { return hits };
+			start = enter;
+			end = exit;
+
+			if !end.is_finite() || grid.count_ray_buckets(&direction, end - start) > body_count {
+				for body_id in world.bodies.iter() {
+					test(body_id, &mut hits);
+				}
+				return hits;
+			}
+		}
+	}
+
+	world.broadphase.raycast(&(origin + direction * start), &direction, end - start, &mut |candidates, exit| {
+		for body_id in candidates.iter() {
+			test(body_id, &mut hits);
+		}
+		// Anything in later groups is further than a hit found before this one
+		first_only && hits.first().is_some_and(|hit| hit.distance <= start + exit)
+	});
+	hits
+}
@@ -0,0 +1,68 @@
+use crate::{Bounds, BodyMap, Geo, Id, Shape, Vec2, World, query::{QueryFilter, get_candidates}, shape::polygons_overlap};
+
+// Every body containing point
+pub fn query_point(world: &World, bodies: &BodyMap, point: &Vec2, filter: &QueryFilter) -> Vec<Id> {
+	let candidates: Vec<Id> = match world.broadphase.as_grid() {
+		Some(grid) => grid.get_bucket_from_world_pos(point).collect(),
+		None => world.broadphase.query_aabb(&Bounds::new(*point, *point)),
+	};
+
+	candidates.into_iter()
+		.filter(|body_id| {
+			let body = bodies.get(body_id).expect("failed to get body in query::query_point");
+			filter.allows(body) && body.contains_point(point)
+		})
+		.collect()
+}
+
+// Every body whose bounds overlap bounds
+pub fn query_aabb(world: &World, bodies: &BodyMap, bounds: &Bounds, filter: &QueryFilter) -> Vec<Id> {
+	get_candidates(world, bounds).into_iter()
+		.filter(|body_id| {
+			let body = bodies.get(body_id).expect("failed to get body in query::query_aabb");
+			filter.allows(body) && body.bounds.overlaps_with(bounds)
+		})
+		.collect()
+}
+
+// Every body overlapping shape placed at position and rotated by angle, without creating a body for it
+pub fn overlap_shape(world: &World, bodies: &BodyMap, shape: &Shape, position: &Vec2, angle: Geo, filter: &QueryFilter) -> Vec<Id> {
+	let vertices = shape.transform(position, angle);
+	let mut bounds = Bounds::empty();
+	bounds.update_from_vertices(&vertices);
+
+	get_candidates(world, &bounds).into_iter()
+		.filter(|body_id| {
+			let body = bodies.get(body_id).expect("failed to get body in query::overlap_shape");
+			filter.allows(body) && body.bounds.overlaps_with(&bounds) && polygons_overlap(&vertices, body.get_vertices())
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Body, BodyOptions, BroadphaseKind, EngineOptions};
+
+	#[test]
+	fn query_point_finds_static_and_dynamic() {
+		for kind in [BroadphaseKind::Grid, BroadphaseKind::Tree] {
+			let mut world = World::from_options(&EngineOptions::new().broadphase(kind));
+			let mut bodies = BodyMap::default();
+			let mut ground = Body::rectangle(400.0, 40.0, Vec2::new(0.0, 0.0), BodyOptions::new().is_static(true));
+			let mut body = Body::rectangle(20.0, 20.0, Vec2::new(-150.0, -10.0), BodyOptions::new());
+			let (ground_id, body_id) = (ground.id, body.id);
+			world.add_body(&mut ground);
+			world.add_body(&mut body);
+			bodies.insert(ground_id, ground);
+			bodies.insert(body_id, body);
+
+			let filter = QueryFilter::default();
+			let mut hits = query_point(&world, &bodies, &Vec2::new(-155.0, -5.0), &filter);
+			hits.sort();
+			assert_eq!(hits, vec![ground_id.min(body_id), ground_id.max(body_id)]);
+			assert_eq!(query_point(&world, &bodies, &Vec2::new(150.0, 0.0), &filter), vec![ground_id]);
+			assert!(query_point(&world, &bodies, &Vec2::new(-150.0, -50.0), &filter).is_empty());
+		}
+	}
+}
@@ -0,0 +1,158 @@
+use serde::Serialize;
+
+use crate::{BodyMap, Geo, Id, Shape, Vec2, World, query::{QueryFilter, get_candidates}, shape::get_swept_bounds};
+
+#[derive(Serialize, Clone)]
+pub struct ShapeCastHit {
+	pub body: Id,
+	pub fraction: Geo, // how far along translation the shape got before touching body, 0 to 1
+	pub position: Vec2, // shape's position when it touches body
+	pub point: Vec2,
+	pub normal: Vec2, // from body toward the shape
+}
+
+// Where two polygons first touch while one of them moves
+pub struct Impact {
+	pub fraction: Geo,
+	pub point: Vec2,
+	pub normal: Vec2, // from target toward moving
+}
+
+// First body the shape touches while moving from position by translation, without rotating
+pub fn shape_cast(world: &World, bodies: &BodyMap, shape: &Shape, position: &Vec2, angle: Geo, translation: &Vec2, filter: &QueryFilter) -> Option<ShapeCastHit> {
+	let vertices = shape.transform(position, angle);
+	let swept_bounds = get_swept_bounds(&vertices, translation);
+
+	let mut best: Option<ShapeCastHit> = None;
+	for body_id in get_candidates(world, &swept_bounds) {
+		let body = bodies.get(&body_id).expect("failed to get body in query::shape_cast");
+		if !filter.allows(body) || !body.bounds.overlaps_with(&swept_bounds) { continue; }
+		let Some(impact) = time_of_impact(&vertices, translation, body.get_vertices()) else { continue };
+
+		if best.as_ref().is_none_or(|best| impact.fraction < best.fraction) {
+			best = Some(ShapeCastHit {
+				body: body_id,
+				fraction: impact.fraction,
+				position: position + translation * impact.fraction,
+				point: impact.point,
+				normal: impact.normal,
+			});
+		}
+	}
+	best
+}
+
+// Swept SAT: finds the fraction of translation at which convex polygon moving first touches convex polygon target
+// If they already overlap, the fraction is 0 and the normal is against translation
+pub fn time_of_impact(moving: &[Vec2], translation: &Vec2, target: &[Vec2]) -> Option<Impact> {
+	let project = |vertices: &[Vec2], axis: &Vec2| {
+		vertices.iter().fold((Geo::MAX, Geo::MIN), |(min, max), vertex| {
+			let proj = vertex.dot(axis);
+			(min.min(proj), max.max(proj))
+		})
+	};
+
+	let mut enter = Geo::NEG_INFINITY; // latest time the projections start overlapping on an axis
+	let mut exit = Geo::INFINITY; // earliest time the projections stop overlapping on an axis
+	let mut normal = Vec2::zero();
+	let mut normal_from_target = false;
+	for (vertices, is_target) in [(target, true), (moving, false)] { // target first, so its faces win ties
+		for (i, cur_vertex) in vertices.iter().enumerate() {
+			let edge = vertices[(i + 1) % vertices.len()] - cur_vertex;
+			if edge.length() == 0.0 { continue; }
+			let axis = edge.normal().normalize();
+
+			let (moving_min, moving_max) = project(moving, &axis);
+			let (target_min, target_max) = project(target, &axis);
+			let speed = translation.dot(&axis);
+
+			let (axis_enter, axis_exit, axis_normal) = if moving_max < target_min { // moving is behind target on this axis
+				if speed <= 0.0 { return None; }
+				((target_min - moving_max) / speed, (target_max - moving_min) / speed, -axis)
+			}
+			else if moving_min > target_max { // moving is in front of target on this axis
+				if speed >= 0.0 { return None; }
+				((target_max - moving_min) / speed, (target_min - moving_max) / speed, axis)
+			}
+			else { // already overlapping on this axis
+				let axis_exit = if speed > 0.0 { (target_max - moving_min) / speed } else if speed < 0.0 { (target_min - moving_max) / speed } else { Geo::INFINITY };
+				(Geo::NEG_INFINITY, axis_exit, axis)
+			};
+
+			if axis_enter > enter {
+				enter = axis_enter;
+				normal = axis_normal;
+				normal_from_target = is_target;
+			}
+			exit = exit.min(axis_exit);
+			if enter > exit || enter > 1.0 { return None; }
+		}
+	}
+
+	if enter == Geo::NEG_INFINITY { // already overlapping
+		let length = translation.length();
+		let normal = if length > 0.0 { -translation / length } else { Vec2::zero() };
+		let point = *moving.iter().max_by(|a, b| a.dot(translation).total_cmp(&b.dot(translation))).unwrap(); // unwrap ok, polygons aren't empty
+		return Some(Impact { fraction: 0.0, point, normal });
+	}
+
+	// Contact is the vertex (or middle of the face) that reaches the other polygon's face first
+	let offset = translation * enter;
+	let point = if normal_from_target {
+		let moved: Vec<Vec2> = moving.iter().map(|vertex| vertex + offset).collect();
+		get_face_center(&moved, &-normal)
+	}
+	else {
+		get_face_center(target, &normal)
+	};
+	Some(Impact { fraction: enter, point, normal })
+}
+
+// Average of the vertices furthest in direction; the middle of the face if one faces that way
+fn get_face_center(vertices: &[Vec2], direction: &Vec2) -> Vec2 {
+	let max = vertices.iter().map(|vertex| vertex.dot(direction)).fold(Geo::MIN, Geo::max);
+	let (sum, count) = vertices.iter()
+		.filter(|vertex| vertex.dot(direction) >= max - 0.01) // 0.01 for precision error, same as Body::contains_point
+		.fold((Vec2::zero(), 0.0), |(sum, count), vertex| (sum + vertex, count + 1.0));
+	sum / count
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::query::test_helpers::{assert_close, square};
+
+	#[test]
+	fn hits_face() {
+		let impact = time_of_impact(square(0.0, 0.0).get_vertices(), &Vec2::new(100.0, 0.0), square(50.0, 0.0).get_vertices()).expect("should hit");
+		assert_close(impact.fraction, 0.3);
+		assert_close(impact.normal.x, -1.0);
+		assert_close(impact.normal.y, 0.0);
+		assert_close(impact.point.x, 40.0);
+		assert_close(impact.point.y, 0.0);
+	}
+
+	#[test]
+	fn hits_corner() {
+		let impact = time_of_impact(square(0.0, 0.0).get_vertices(), &Vec2::new(100.0, 100.0), square(50.0, 50.0).get_vertices()).expect("should hit");
+		assert_close(impact.fraction, 0.3);
+	}
+
+	#[test]
+	fn misses() {
+		assert!(time_of_impact(square(0.0, 0.0).get_vertices(), &Vec2::new(0.0, 100.0), square(50.0, 0.0).get_vertices()).is_none(), "moving past");
+		assert!(time_of_impact(square(0.0, 0.0).get_vertices(), &Vec2::new(20.0, 0.0), square(50.0, 0.0).get_vertices()).is_none(), "stopping short");
+		assert!(time_of_impact(square(0.0, 0.0).get_vertices(), &Vec2::new(-100.0, 0.0), square(50.0, 0.0).get_vertices()).is_none(), "moving away");
+	}
+
+	#[test]
+	fn already_overlapping() {
+		let impact = time_of_impact(square(0.0, 0.0).get_vertices(), &Vec2::new(100.0, 0.0), square(15.0, 0.0).get_vertices()).expect("should hit");
+		assert_close(impact.fraction, 0.0);
+		assert_close(impact.normal.x, -1.0);
+		assert_close(impact.normal.y, 0.0);
+
+		let impact = time_of_impact(square(0.0, 0.0).get_vertices(), &Vec2::zero(), square(15.0, 0.0).get_vertices()).expect("should hit without moving");
+		assert_close(impact.fraction, 0.0);
+	}
+}
@@ -0,0 +1,10 @@
+use crate::{Body, BodyOptions, Geo, Vec2};
+
+// 20 by 20 dynamic box centered on (x, y)
+pub fn square(x: Geo, y: Geo) -> Body {
+	Body::rectangle(20.0, 20.0, Vec2::new(x, y), BodyOptions::default())
+}
+
+pub fn assert_close(actual: Geo, expected: Geo) {
+	assert!((actual - expected).abs() < 0.001, "expected {}, got {}", expected, actual);
+}
@@ -0,0 +1,133 @@
+use serde::Deserialize;
+use wasm_bindgen::JsValue;
+
+use crate::{Body, BodyMap, Bounds, Geo, Id, Vec2};
+
+// Convex geometry that isn't attached to a body, used by queries. Vertices are relative to the shape's origin
+#[derive(Clone)]
+pub struct Shape {
+	pub vertices: Vec<Vec2>,
+}
+
+impl Shape {
+	//
+	// constructors
+	//
+
+	pub fn polygon(vertices: Vec<Vec2>) -> Shape {
+		assert!(vertices.len() >= 3); // There should be at least 3 vertices for a valid shape
+		Shape { vertices }
+	}
+	pub fn rectangle(width: Geo, height: Geo) -> Shape {
+		let half_width = width / 2.0;
+		let half_height = height / 2.0;
+		Shape::polygon(vec![
+			Vec2::new(-half_width, -half_height), // top left
+			Vec2::new( half_width, -half_height), // top right
+			Vec2::new( half_width,  half_height), // bottom right
+			Vec2::new(-half_width,  half_height), // bottom left
+		])
+	}
+	pub fn circle(radius: Geo) -> Shape {
+		let mut vertices = Vec::new();
+		let vertice_count = ((radius.powf(0.333) * 8.0).round() as u32).max(3); // tiny circles are triangles, not invalid
+
+		let start_angle = crate::TAU * 2.0 / vertice_count as Geo;
+		for i in 0..vertice_count {
+			vertices.push(Vec2::new((start_angle * i as Geo + start_angle / 2.0).cos() * radius, (start_angle * i as Geo + start_angle / 2.0).sin() * radius));
+		}
+		Shape::polygon(vertices)
+	}
+	// Copy of a body's geometry, with its origin at the body's position and unrotated
+	pub fn from_body(body: &Body) -> Shape {
+		let vertices = body.get_vertices().iter()
+			.map(|vertex| (vertex - body.get_position()).rotate(-body.get_angle()))
+			.collect();
+		Shape { vertices }
+	}
+
+	// World space vertices with the shape's origin at position, rotated by angle
+	pub fn transform(&self, position: &Vec2, angle: Geo) -> Vec<Vec2> {
+		self.vertices.iter()
+			.map(|vertex| vertex.rotate(angle) + position)
+			.collect()
+	}
+}
+
+// A shape as described from JS, e.g. { type: "circle", radius: 20 }
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ShapeDef {
+	Rectangle { width: Geo, height: Geo },
+	Circle { radius: Geo },
+	Polygon { vertices: Vec<Vec2> },
+	Body { body: Id }, // geometry of an existing body
+}
+
+impl ShapeDef {
+	// Returns None if the shape is invalid or refers to a body that doesn't exist
+	pub fn to_shape(&self, bodies: &BodyMap) -> Option<Shape> {
+		match self {
+			ShapeDef::Rectangle { width, height } => Some(Shape::rectangle(*width, *height)),
+			ShapeDef::Circle { radius } => if radius.is_finite() && *radius > 0.0 { Some(Shape::circle(*radius)) } else { None },
+			ShapeDef::Polygon { vertices } => if vertices.len() >= 3 { Some(Shape::polygon(vertices.clone())) } else { None },
+			ShapeDef::Body { body } => bodies.get(body).map(Shape::from_body),
+		}
+	}
+	// Body the shape was copied from, if any
+	pub fn get_body(&self) -> Option<Id> {
+		match self {
+			ShapeDef::Body { body } => Some(*body),
+			_ => None,
+		}
+	}
+	pub fn from_js(value: JsValue) -> Option<ShapeDef> {
+		serde_wasm_bindgen::from_value(value)
+			.map_err(|error| web_sys::console::warn_1(&format!("Failed to parse shape: {}.", error).into()))
+			.ok()
+	}
+}
+
+// Bounds covering vertices both where they are and after moving by translation
+pub fn get_swept_bounds(vertices: &[Vec2], translation: &Vec2) -> Bounds {
+	let mut bounds = Bounds::empty();
+	bounds.update_from_vertices(vertices);
+	let moved_min = bounds.min + translation;
+	let moved_max = bounds.max + translation;
+	bounds.expand_to(&moved_min);
+	bounds.expand_to(&moved_max);
+	bounds
+}
+
+// SAT: whether two convex polygons overlap, same as collisions::collides but without needing bodies
+pub fn polygons_overlap(a: &[Vec2], b: &[Vec2]) -> bool {
+	let project = |vertices: &[Vec2], axis: &Vec2| {
+		vertices.iter().fold((Geo::MAX, Geo::MIN), |(min, max), vertex| {
+			let proj = vertex.dot(axis);
+			(min.min(proj), max.max(proj))
+		})
+	};
+	[a, b].iter().all(|vertices| {
+		(0..vertices.len()).all(|i| {
+			let axis = (vertices[(i + 1) % vertices.len()] - vertices[i]).normal();
+			let (a_min, a_max) = project(a, &axis);
+			let (b_min, b_max) = project(b, &axis);
+			a_max >= b_min && a_min <= b_max
+		})
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn invalid_circles() {
+		let bodies = BodyMap::default();
+		for radius in [0.0, -5.0, Geo::NAN, Geo::INFINITY] {
+			assert!(ShapeDef::Circle { radius }.to_shape(&bodies).is_none(), "radius {} should be rejected", radius);
+		}
+		let tiny = ShapeDef::Circle { radius: 0.001 }.to_shape(&bodies).expect("tiny circles are still valid");
+		assert_eq!(tiny.vertices.len(), 3);
+	}
+}
@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsValue;
+
+use crate::{Body, BodyMap, BodyOptions, Geo, Id, Vec2};
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct TilemapOptions {
+	pub position: Vec2, // world position of the top left corner of the first tile
+	pub body: BodyOptions, // used for every merged rectangle. Always made static
+}
+
+impl Default for TilemapOptions {
+	fn default() -> Self {
+		Self {
+			position: Vec2::zero(),
+			body: BodyOptions::new().is_static(true),
+		}
+	}
+}
+
+impl From<JsValue> for TilemapOptions {
+	fn from(value: JsValue) -> TilemapOptions {
+		if value.is_null_or_undefined() {
+			return TilemapOptions::default();
+		}
+
+		serde_wasm_bindgen::from_value(value)
+			.unwrap_or_else(|error| {
+				web_sys::console::warn_1(&format!("Failed to parse TilemapOptions: {}. Using defaults.", error).into());
+				TilemapOptions::default()
+			})
+	}
+}
+
+// Rectangle of solid tiles, in tiles
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TileRect {
+	pub x: usize,
+	pub y: usize,
+	pub width: usize,
+	pub height: usize,
+}
+
+// Ids of the bodies created by Tilemap::create, one per merged rectangle of solid tiles
+#[derive(Serialize)]
+pub struct Tilemap {
+	pub bodies: Vec<Id>,
+}
+
+impl Tilemap {
+	// Creates static bodies covering the solid (non-zero) tiles. Like any other body, they aren't in the world until they're added
+	// tiles is row by row, columns tiles wide. Tiles are merged into rectangles first, so there's no body per tile
+	pub fn create(bodies: &mut BodyMap, tiles: &[u8], columns: usize, tile_size: Geo, options: TilemapOptions) -> Tilemap {
+		let mut tilemap = Tilemap {
+			bodies: Vec::new(),
+		};
+		if columns == 0 || tile_size <= 0.0 { return tilemap } // nothing to cover
+
+		let body_options = options.body.is_static(true);
+		for rect in Tilemap::merge_tiles(tiles, columns) {
+			let size = Vec2::new(rect.width as Geo, rect.height as Geo) * tile_size;
+			let center = options.position + Vec2::new(rect.x as Geo, rect.y as Geo) * tile_size + size / 2.0;
+			let body = Body::rectangle(size.x, size.y, center, body_options.clone());
+			tilemap.bodies.push(body.id);
+			bodies.insert(body.id, body);
+		}
+
+		tilemap
+	}
+
+	// Splits every row into runs of solid tiles, and stacks a run onto the rectangle above it if that covers exactly the same columns
+	// Runs are never split up, so every floor is a single flat surface without seams between tiles for bodies to catch on
+	// Walls can still have seams: where rectangles of different widths are stacked, their shared side is made of separate faces
+	// that a body sliding along it can catch on. Outline walls like that with an EdgeChain instead if it matters
+	pub fn merge_tiles(tiles: &[u8], columns: usize) -> Vec<TileRect> {
+		let mut rects: Vec<TileRect> = Vec::new();
+		let mut open: Vec<usize> = Vec::new(); // rects that reached the previous row
+		for (y, row) in tiles.chunks(columns).enumerate() {
+			let mut next_open = Vec::new();
+			let mut x = 0;
+			while x < row.len() {
+				if row[x] == 0 {
+					x += 1;
+					continue;
+				}
+				let start = x;
+				while x < row.len() && row[x] != 0 { x += 1; }
+				let width = x - start;
+
+				match open.iter().find(|index| rects[**index].x == start && rects[**index].width == width) {
+					Some(&index) => {
+						rects[index].height += 1;
+						next_open.push(index);
+					},
+					None => {
+						rects.push(TileRect { x: start, y, width, height: 1 });
+						next_open.push(rects.len() - 1);
+					},
+				}
+			}
+			open = next_open;
+		}
+		rects
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rect(x: usize, y: usize, width: usize, height: usize) -> TileRect {
+		TileRect { x, y, width, height }
+	}
+
+	#[test]
+	fn merges_rows_and_columns() {
+		let tiles = [
+			0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+			0, 0, 0, 0, 0, 1, 0, 0, 0, 0,
+			1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+			1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+			1, 0, 0, 2, 0, 0, 1, 1, 1, 1,
+			1, 0, 0, 1, 0, 0, 1, 1, 1, 1,
+		];
+		assert_eq!(Tilemap::merge_tiles(&tiles, 10), vec![
+			rect(5, 1, 1, 1),
+			rect(0, 2, 10, 2),
+			rect(0, 4, 1, 2),
+			rect(3, 4, 1, 2),
+			rect(6, 4, 4, 2),
+		]);
+	}
+
+	#[test]
+	fn only_stacks_matching_runs() {
+		let tiles = [
+			1, 1, 1,
+			1, 1, 0,
+			1, 1, 0,
+		];
+		assert_eq!(Tilemap::merge_tiles(&tiles, 3), vec![rect(0, 0, 3, 1), rect(0, 1, 2, 2)]);
+	}
+
+	#[test]
+	fn empty_and_partial_rows() {
+		assert_eq!(Tilemap::merge_tiles(&[], 4), vec![]);
+		assert_eq!(Tilemap::merge_tiles(&[0, 0, 0, 0], 4), vec![]);
+		assert_eq!(Tilemap::merge_tiles(&[0, 1, 1, 0, 1, 1], 4), vec![rect(1, 0, 2, 1), rect(0, 1, 2, 1)]);
+	}
+}
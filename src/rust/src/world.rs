@@ -1,79 +1,118 @@
 use std::collections::{HashMap, HashSet};
 
-use crate::{Body, BodyMap, CollisionPair, Frame, Grid, Id, PairId, Time, Vec2, grid::BucketSize};
+use nohash_hasher::BuildNoHashHasher;
+
+use crate::{Body, BodyMap, Broadphase, BroadphaseKind, CollisionPair, EngineOptions, Event, Frame, Geo, Grid, Id, Joint, JointMap, PairId, Time, Vec2, collision_pair::DisabledPair, grid::{BucketSize, GridHashMap}};
+
+// Decides whether a touching or speculative pair is solved this step, and can change its friction / restitution
+pub type PreSolve = Box<dyn Fn(&mut CollisionPair) -> bool>;
 
 pub struct World {
 	pub gravity: Vec2,
+	pub air_drag: Geo, // damping applied to every body on top of its own linear / angular damping
+	pub ccd_dynamic: bool, // whether bullets are also swept against dynamic bodies, not just static ones
+	pub speculative_contacts: bool, // whether to add contacts for pairs that are about to touch, see collisions::create_speculative_manifold
 	pub bodies: HashSet<Id>,
 	pub frame: Frame,
 	pub time: Time,
-	pub collision_pairs: HashSet<CollisionPair>,
-	pub grid: Grid,
+	pub collision_pairs: HashMap<PairId, CollisionPair>, // keyed by CollisionPair::key
+	pub joints: JointMap,
+	pub record_events: bool, // whether steps queue collision events. Off by default, since there's one per touching pair every step and nothing clears the queue unless JS calls Engine::drain_events
+	pub events: Vec<Event>,
+	pub pre_solve: Option<PreSolve>, // called for every touching or speculative pair during collisions::find, see Engine::world_set_pre_solve
+	pub disabled_pairs: HashMap<PairId, DisabledPair>, // touching pairs pre_solve disabled last step. They're not in collision_pairs, but haven't ended either
+	pub broadphase: Box<dyn Broadphase>,
+	pub pair_buffer: Vec<(Id, Id)>, // broadphase pairs, kept between steps so the allocation is reused
 }
 
 impl World {
-	pub fn new(bucket_size: BucketSize) -> Self {
+	pub fn new(broadphase: Box<dyn Broadphase>) -> Self {
 		Self {
 			gravity: Vec2::new(0.0, 300.0),
+			air_drag: 0.0,
+			ccd_dynamic: false,
+			speculative_contacts: false,
 			bodies: HashSet::new(),
 			frame: 0,
 			time: 0.0,
-			collision_pairs: HashSet::new(),
-			grid: Grid::new(bucket_size),
+			collision_pairs: HashMap::new(),
+			joints: HashMap::with_hasher(BuildNoHashHasher::default()),
+			record_events: false,
+			events: Vec::new(),
+			pre_solve: None,
+			disabled_pairs: HashMap::new(),
+			broadphase,
+			pair_buffer: Vec::new(),
 		}
 	}
+	pub fn from_options(options: &EngineOptions) -> Self {
+		let mut world = World::new(BroadphaseKind::create(options));
+		world.bodies.reserve(options.capacity);
+		world
+	}
 	pub fn add_body(&mut self, body: &mut Body) {
 		if self.bodies.contains(&body.id) { return; }
 		self.bodies.insert(body.id);
-		self.grid.insert_body(body);
+		self.broadphase.insert(body);
 	}
 	pub fn remove_body(&mut self, body: &mut Body) {
 		self.bodies.remove(&body.id);
-		self.grid.remove_body(body);
+		self.broadphase.remove(body);
 	}
-	pub fn find_pairs(&self, bodies: &BodyMap) -> Vec<(Id, Id)> {
-		/*
-		- Iterate through all grid buckets
-		- Std pairing algo for each bucket
-		- Merge all pairs together; Shouldn't be any dupes (can use pair ID with hashmap / set to check)
-		*/
-		let mut pairs: HashMap<PairId, (Id, Id)> = HashMap::new();
-
-		for (_bucket_id, bucket) in self.grid.get_bucket_iter() {
-			self.pair_bucket(bucket, &mut pairs, &bodies);
+	// Queues event for JS if collision events are being recorded
+	pub fn queue_event(&mut self, event: Event) {
+		if self.record_events {
+			self.events.push(event);
 		}
-
-		pairs.into_iter()
-			.map(|pair| pair.1)
+	}
+	pub fn add_joint(&mut self, joint: Joint) {
+		self.joints.insert(joint.id, joint);
+	}
+	pub fn remove_joint(&mut self, joint_id: Id) -> Option<Joint> {
+		self.joints.remove(&joint_id)
+	}
+	// Keys (see CollisionPair::key) of pairs of bodies that are connected by a joint and shouldn't collide with each other
+	pub fn get_jointed_pairs(&self) -> HashSet<PairId> {
+		self.joints.values()
+			.filter(|joint| !joint.collide_connected)
+			.map(|joint| CollisionPair::key(joint.body_a, joint.body_b))
 			.collect()
 	}
-	fn pair_bucket(&self, bucket: &Vec<Id>, pairs: &mut HashMap<PairId, (Id, Id)>, bodies: &BodyMap) {
-		let len = bucket.len();
-
-		for i in 0..len - 1 {
-			let body_a_id = bucket[i];
-			let body_a = bodies.get(&body_a_id).expect(&format!("Failed to get body_a {body_a_id} in World::pair_bucket"));
-			for j in i + 1..len {
-				let body_b_id = bucket[j];
-				let pair_id = CollisionPair::pair_id(body_a_id, body_b_id);
-				if pairs.contains_key(&pair_id) { continue; } // already in pairs
+	// Replaces the contents of pairs with the broadphase's pairs
+	pub fn find_pairs(&self, bodies: &BodyMap, pairs: &mut Vec<(Id, Id)>) {
+		pairs.clear();
+		self.broadphase.pairs(bodies, pairs);
+	}
 
-				let body_b = bodies.get(&body_b_id).expect(&format!("Failed to get body_b {body_b_id} in World::pair_bucket"));
-				if body_a.bounds.overlaps_with(&body_b.bounds) {
-					pairs.insert(pair_id, (body_a_id, body_b_id));
-				}
-			}
+	// Only bodies in the world; bodies that were removed or never added stay out of the broadphase
+	pub fn update_broadphase(&mut self, bodies: &mut BodyMap) {
+		for body_id in self.bodies.iter() {
+			let body = bodies.get_mut(body_id).expect("failed to get body in World::update_broadphase");
+			if body.is_static { continue; }
+			self.broadphase.update(body);
 		}
 	}
 
-	pub fn update_grid(&mut self, bodies: &mut BodyMap) {
-		for (_id, body) in bodies.iter_mut() {
-			if body.is_static { continue; }
-			self.grid.update_body(body);
+	// Replaces the grid with one using bucket_size, and puts every body back in it. Does nothing if the broadphase isn't a grid
+	pub fn set_bucket_size(&mut self, bucket_size: BucketSize, bodies: &mut BodyMap) {
+		let Some(grid) = self.broadphase.as_grid() else { return };
+		if bucket_size <= 0.0 || !bucket_size.is_finite() {
+			web_sys::console::warn_1(&format!("Invalid bucket size {}, keeping {}.", bucket_size, grid.bucket_size).into());
+			return;
+		}
+
+		let mut grid = Grid::new(bucket_size);
+		for body_id in self.bodies.iter() {
+			let body = bodies.get_mut(body_id).expect("failed to get body in World::set_bucket_size");
+			body.grid_spaces.clear(); // old grid is gone, nothing to remove it from
+			body.grid_bounds = None;
+			grid.insert_body(body);
 		}
+		self.broadphase = Box::new(grid);
 	}
 
-	pub fn get_buckets(&self) -> (BucketSize, &crate::grid::GridHashMap) {
-		(self.grid.bucket_size, &self.grid.buckets)
+	// Bucket size, dynamic buckets and static buckets. None if the broadphase isn't a grid
+	pub fn get_buckets(&self) -> Option<(BucketSize, &GridHashMap, &GridHashMap)> {
+		self.broadphase.as_grid().map(|grid| (grid.bucket_size, &grid.buckets, &grid.static_buckets))
 	}
 }
\ No newline at end of file